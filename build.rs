@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
@@ -30,7 +31,7 @@ fn parse_offset(raw_offset: &str) -> i32 {
     };
 
     let value = (hours * HOUR) + (minutes * 60);
-    return if is_west { value as i32 * -1 } else { value as i32 };
+    if is_west { -(value as i32) } else { value as i32 }
 }
 
 fn main() {
@@ -40,14 +41,23 @@ fn main() {
     let mut file = BufWriter::new(File::create(&path).unwrap());
 
     let mut builder: phf_codegen::Map<String> = phf_codegen::Map::new();
+    // Every offset of each abbreviation, the preferred (uncommented) one first
+    let mut all_offsets: BTreeMap<String, Vec<i32>> = BTreeMap::new();
 
     for line in raw_tz.lines() {
         let line = line.unwrap();
-        if line.starts_with('#') {
-            continue;
-        }
+        // Commented lines are alternate meanings of an abbreviation that's defined elsewhere in the file
+        let (alternate, line) = match line.strip_prefix('#') {
+            Some(rest) => (true, rest.trim_start().to_string()),
+            None => (false, line),
+        };
 
-        let capture = FULL_PATTERN.captures(&line).expect("RegEx failed to match line");
+        let capture = match FULL_PATTERN.captures(&line) {
+            Some(capture) => capture,
+            // Any other comment is just a comment
+            None if alternate => continue,
+            None => panic!("RegEx failed to match line"),
+        };
 
         let abbreviation = capture.get(1).unwrap().as_str();
         let raw_offset = capture.get(2).unwrap().as_str();
@@ -58,7 +68,18 @@ fn main() {
             0
         };
 
-        builder.entry(String::from(abbreviation), &format!("\"{}\"", offset).to_string());
+        let offsets = all_offsets.entry(String::from(abbreviation)).or_default();
+        if alternate {
+            offsets.push(offset);
+        } else {
+            offsets.insert(0, offset);
+            builder.entry(String::from(abbreviation), &format!("\"{}\"", offset).to_string());
+        }
+    }
+
+    let mut all_builder: phf_codegen::Map<String> = phf_codegen::Map::new();
+    for (abbreviation, offsets) in &all_offsets {
+        all_builder.entry(abbreviation.clone(), &format!("&{:?}", offsets));
     }
 
     write!(
@@ -67,5 +88,13 @@ fn main() {
         builder.build()
     )
         .unwrap();
-    write!(&mut file, ";\n").unwrap();
+    writeln!(&mut file, ";").unwrap();
+
+    write!(
+        &mut file,
+        "static TIMEZONE_OFFSETS_ALL: phf::Map<&'static str, &'static [i32]> = {}",
+        all_builder.build()
+    )
+        .unwrap();
+    writeln!(&mut file, ";").unwrap();
 }
\ No newline at end of file
@@ -7,25 +7,63 @@ include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
     Parse an abbreviation of a timezone into a UTC offset.
     Note: This is not standardized at all and is simply built on a reference of Time Zone abbreviations
     from Wikipedia (as of 2023-7-20).
+    Lookup is case-insensitive; every abbreviation in the table is uppercase (enforced by build.rs),
+    so no two abbreviations are distinguished by case alone.
+
+    Many abbreviations are ambiguous (CST is Central Standard Time, China Standard Time and Cuba Standard Time).
+    The preferred meaning is the uncommented line in `abbr_tz`; the other meanings are listed there commented out,
+    and are only returned by `parse_abbreviation_all`. To prefer another meaning, swap which line is commented.
  */
 pub fn parse_abbreviation(abbreviation: &str) -> Result<FixedOffset, String> {
-    let offset_integer_string = TIMEZONES.get(abbreviation);
-    if offset_integer_string.is_none() {
-        return Err("Failed to find abbreviation".to_string());
-    }
+    let upper = abbreviation.to_uppercase();
+    let offset_integer_string = TIMEZONES.get(upper.as_str()).ok_or("Failed to find abbreviation".to_string())?;
 
-    let offset = FixedOffset::east_opt(offset_integer_string.unwrap().parse().expect("Failed to parse stored offset"));
-    return offset.ok_or("Failed to parse offset".to_string());
+    let offset = FixedOffset::east_opt(offset_integer_string.parse().expect("Failed to parse stored offset"));
+    if TIMEZONE_OFFSETS_ALL.get(upper.as_str()).is_some_and(|all| all.len() > 1) {
+        tracing::debug!("{} is ambiguous, using the preferred offset", abbreviation);
+    }
+    offset.ok_or("Failed to parse offset".to_string())
 }
 
+/// Every known UTC offset of an abbreviation, the preferred one (as returned by `parse_abbreviation`) first.
+#[allow(dead_code)]
+pub fn parse_abbreviation_all(abbreviation: &str) -> Result<Vec<FixedOffset>, String> {
+    let offsets = TIMEZONE_OFFSETS_ALL.get(abbreviation.to_uppercase().as_str()).ok_or("Failed to find abbreviation".to_string())?;
+    offsets.iter()
+        .map(|offset| FixedOffset::east_opt(*offset).ok_or("Failed to parse offset".to_string()))
+        .collect()
+}
 
 #[cfg(test)]
 mod tests {
     use chrono::FixedOffset;
-    use crate::abbr::parse_abbreviation;
+    use crate::abbr::{parse_abbreviation, parse_abbreviation_all};
 
     #[test]
     fn parse_offset() {
         assert_eq!(parse_abbreviation("CST").unwrap(), FixedOffset::west_opt(6 * 3600).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_offset_case_insensitive() {
+        assert_eq!(parse_abbreviation("cst").unwrap(), FixedOffset::west_opt(6 * 3600).unwrap());
+        assert_eq!(parse_abbreviation("Cst").unwrap(), FixedOffset::west_opt(6 * 3600).unwrap());
+        assert_eq!(parse_abbreviation("jSt").unwrap(), FixedOffset::east_opt(9 * 3600).unwrap());
+        assert_eq!(parse_abbreviation("Utc").unwrap(), FixedOffset::east_opt(0).unwrap());
+        assert_eq!(parse_abbreviation("pst").unwrap(), FixedOffset::west_opt(8 * 3600).unwrap());
+        // Unknown abbreviations are still reported as such, whatever their case
+        assert_eq!(parse_abbreviation("xyz"), Err("Failed to find abbreviation".to_string()));
+    }
+
+    #[test]
+    fn ambiguous_abbreviations() {
+        let hours = |hours: f32| FixedOffset::east_opt((hours * 3600.0) as i32).unwrap();
+
+        // CST always means Central Standard Time, no matter where its other meanings are in the table
+        assert_eq!(parse_abbreviation("CST").unwrap(), hours(-6.0));
+        assert_eq!(parse_abbreviation_all("cst").unwrap(), vec![hours(-6.0), hours(8.0), hours(-5.0)]);
+        assert_eq!(parse_abbreviation_all("IST").unwrap(), vec![hours(2.0), hours(5.5), hours(1.0)]);
+        assert_eq!(parse_abbreviation_all("JST").unwrap(), vec![hours(9.0)]);
+        assert!(parse_abbreviation_all("xyz").is_err());
+    }
+}
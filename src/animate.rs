@@ -0,0 +1,80 @@
+#[cfg(feature = "raster")]
+use axum::body::Bytes;
+#[cfg(feature = "raster")]
+use chrono::Duration;
+
+#[cfg(feature = "raster")]
+use crate::color::Rgb;
+#[cfg(feature = "raster")]
+use crate::encode::GifAnimation;
+#[cfg(feature = "raster")]
+use crate::error::TimeBannerError;
+#[cfg(feature = "raster")]
+use crate::raster::{MAX_OUTPUT_PIXELS, Rasterizer};
+#[cfg(feature = "raster")]
+use crate::template::{render_template, RenderContext};
+
+/// Frames in an animated banner when `?frames=` isn't given, and the most that may be requested.
+pub const DEFAULT_FRAMES: usize = 60;
+pub const MAX_FRAMES: usize = 60;
+
+/// How long each frame is shown, in hundredths of a second.
+#[cfg(feature = "raster")]
+const FRAME_DELAY: u16 = 100;
+
+/// Parse a requested frame count, rejecting anything outside 1 to `MAX_FRAMES`.
+pub fn parse_frames(raw: &str) -> Result<usize, String> {
+    match raw.parse::<usize>() {
+        Ok(frames) if (1..=MAX_FRAMES).contains(&frames) => Ok(frames),
+        _ => Err(format!("Frames must be between 1 and {}", MAX_FRAMES)),
+    }
+}
+
+/// Render an animated GIF counting along with the banner: frame `n` shows the banner as it will read `n` seconds
+/// from now. Every frame is rasterized by the same `Rasterizer`, and encoded as soon as it's drawn. All the frames
+/// together may have no more pixels than the largest still image.
+#[cfg(feature = "raster")]
+pub fn render_animation(rasterizer: &Rasterizer, context: RenderContext, frames: usize, dpi: f32, scale: f32, fill: Option<Rgb>) -> Result<Bytes, TimeBannerError> {
+    let rasterize_error = |message: Option<String>| TimeBannerError::RasterizeError(message.unwrap_or("Unknown error".to_string()));
+
+    let mut animation: Option<GifAnimation> = None;
+    for frame in 0..frames {
+        // Moving the time back is the same as moving the clock forward
+        let context = RenderContext { value: context.value - Duration::seconds(frame as i64), ..context.clone() };
+        let rendered = render_template(context)
+            .map_err(|e| TimeBannerError::RenderError(format!("Template Could Not Be Rendered :: {}", e)))?;
+        let pixmap = rasterizer.render(rendered.into_bytes(), dpi, scale, fill)?;
+
+        let animation = match animation.as_mut() {
+            Some(animation) => animation,
+            None => {
+                let pixels = pixmap.width() as u64 * pixmap.height() as u64 * frames as u64;
+                if pixels > MAX_OUTPUT_PIXELS {
+                    return Err(TimeBannerError::ParseError(format!(
+                        "Animation would be {} frames of {}x{} pixels, over the limit of {} in all; lower the frames, DPI, scale or size",
+                        frames, pixmap.width(), pixmap.height(), MAX_OUTPUT_PIXELS)));
+                }
+                animation.insert(GifAnimation::new(pixmap.width(), pixmap.height(), FRAME_DELAY, frames > 1).map_err(|e| rasterize_error(e.message))?)
+            }
+        };
+        animation.push(&pixmap).map_err(|e| rasterize_error(e.message))?;
+    }
+
+    animation.ok_or(rasterize_error(Some("No frames to encode".to_string())))?
+        .finish()
+        .map_err(|e| rasterize_error(e.message))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animate::{MAX_FRAMES, parse_frames};
+
+    #[test]
+    fn frame_counts() {
+        assert_eq!(parse_frames("1"), Ok(1));
+        assert_eq!(parse_frames("30"), Ok(30));
+        assert!(parse_frames("0").is_err());
+        assert!(parse_frames(&(MAX_FRAMES + 1).to_string()).is_err());
+        assert!(parse_frames("many").is_err());
+    }
+}
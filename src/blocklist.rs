@@ -0,0 +1,100 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::{ConnectInfo, State};
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use ipnet::IpNet;
+use regex::RegexSet;
+
+use crate::config::Configuration;
+use crate::error::{get_error_response, TimeBannerError};
+use crate::state::AppState;
+
+/// Operator-configured hard blocks on client networks and request paths.
+pub struct Blocklist {
+    networks: Vec<IpNet>,
+    paths: RegexSet,
+}
+
+impl Blocklist {
+    /// Build the blocklist from configuration, failing on any invalid CIDR or path pattern.
+    pub fn from_config(config: &Configuration) -> Result<Self, String> {
+        let networks = config.blocked_networks.iter()
+            .map(|raw| raw.trim().parse::<IpNet>()
+                // Allow single addresses without a prefix length
+                .or_else(|_| raw.trim().parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("Invalid blocked network: {}", raw)))
+            .collect::<Result<Vec<IpNet>, String>>()?;
+
+        let paths = RegexSet::new(&config.blocked_paths)
+            .map_err(|e| format!("Invalid blocked path pattern: {}", e))?;
+
+        Ok(Self { networks, paths })
+    }
+
+    pub fn is_blocked(&self, ip: IpAddr, path: &str) -> bool {
+        self.networks.iter().any(|network| network.contains(&ip)) || self.paths.is_match(path)
+    }
+}
+
+/// Middleware rejecting requests from blocked networks or for blocked paths with a 403.
+pub async fn blocklist_middleware<B>(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if state.blocklist.is_blocked(addr.ip(), request.uri().path()) {
+        tracing::debug!("Blocked request from {} for {}", addr.ip(), request.uri().path());
+        return get_error_response(TimeBannerError::Forbidden).into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use crate::blocklist::Blocklist;
+    use crate::config::Configuration;
+
+    fn blocklist(vars: Vec<(&str, &str)>) -> Result<Blocklist, String> {
+        let config = Configuration::from_vars(vars).unwrap();
+        Blocklist::from_config(&config)
+    }
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn blocked_network() {
+        let blocklist = blocklist(vec![("BLOCKED_NETWORKS", "10.0.0.0/8,2001:db8::/32,192.168.1.7")]).unwrap();
+        assert!(blocklist.is_blocked(ip("10.1.2.3"), "/relative/0"));
+        assert!(blocklist.is_blocked(ip("2001:db8::1"), "/relative/0"));
+        assert!(blocklist.is_blocked(ip("192.168.1.7"), "/relative/0"));
+        assert!(!blocklist.is_blocked(ip("192.168.1.8"), "/relative/0"));
+        assert!(!blocklist.is_blocked(ip("11.0.0.1"), "/relative/0"));
+    }
+
+    #[test]
+    fn blocked_path() {
+        let blocklist = blocklist(vec![("BLOCKED_PATHS", "^/relative/999,\\.png$")]).unwrap();
+        assert!(blocklist.is_blocked(ip("127.0.0.1"), "/relative/999"));
+        assert!(blocklist.is_blocked(ip("127.0.0.1"), "/absolute/0.png"));
+        assert!(!blocklist.is_blocked(ip("127.0.0.1"), "/absolute/0.svg"));
+    }
+
+    #[test]
+    fn empty_blocklist() {
+        let blocklist = blocklist(vec![]).unwrap();
+        assert!(!blocklist.is_blocked(ip("10.1.2.3"), "/relative/0"));
+    }
+
+    #[test]
+    fn invalid_network() {
+        assert!(blocklist(vec![("BLOCKED_NETWORKS", "10.0.0.0/99")]).is_err());
+        assert!(blocklist(vec![("BLOCKED_PATHS", "(")]).is_err());
+    }
+}
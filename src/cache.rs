@@ -0,0 +1,88 @@
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+
+struct CacheEntry<V> {
+    value: V,
+    inserted: Instant,
+}
+
+/// A thread-safe LRU cache whose entries also expire after a fixed time-to-live.
+/// Expired entries are treated as misses and evicted on lookup.
+pub struct RenderCache<K: Hash + Eq, V: Clone> {
+    entries: Mutex<LruCache<K, CacheEntry<V>>>,
+    ttl: Duration,
+}
+
+impl<K: Hash + Eq, V: Clone> RenderCache<K, V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let expired = match entries.get(key) {
+            Some(entry) => entry.inserted.elapsed() > self.ttl,
+            None => return None,
+        };
+
+        if expired {
+            entries.pop(key);
+            return None;
+        }
+
+        entries.get(key).map(|entry| entry.value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let entry = CacheEntry { value, inserted: Instant::now() };
+        self.entries.lock().unwrap().put(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+    use crate::cache::RenderCache;
+
+    #[test]
+    fn cache_hit() {
+        let cache = RenderCache::new(4, Duration::from_secs(60));
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+    }
+
+    #[test]
+    fn cache_evicts_least_recent() {
+        let cache = RenderCache::new(2, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.get(&"a");
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn cache_entry_expires() {
+        let cache = RenderCache::new(4, Duration::from_millis(20));
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        sleep(Duration::from_millis(40));
+        assert_eq!(cache.get(&"a"), None);
+    }
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+/// Chat platforms with their own markup for rendering live, localized timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatPlatform {
+    Discord,
+    Slack,
+}
+
+impl FromStr for ChatPlatform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "discord" => Ok(ChatPlatform::Discord),
+            "slack" => Ok(ChatPlatform::Slack),
+            _ => Err(format!("Unknown chat platform: {}", s)),
+        }
+    }
+}
+
+/// Map a Discord style letter to the equivalent Slack date format tokens.
+fn slack_tokens(style: &str) -> Option<&'static str> {
+    match style {
+        "t" => Some("{time}"),
+        "T" => Some("{time_secs}"),
+        "d" => Some("{date_num}"),
+        "D" => Some("{date}"),
+        "f" => Some("{date} {time}"),
+        "F" => Some("{date_long} {time}"),
+        "R" => Some("{ago}"),
+        _ => None,
+    }
+}
+
+/// Produce the chat markup for a timestamp.
+/// Styles use Discord's letters (t, T, d, D, f, F, R), which are translated for other platforms.
+pub fn format_chat_timestamp(time: DateTime<Utc>, style: &str, platform: ChatPlatform) -> Result<String, String> {
+    let tokens = slack_tokens(style).ok_or(format!("Unknown timestamp style: {}", style))?;
+    let epoch = time.timestamp();
+
+    Ok(match platform {
+        ChatPlatform::Discord => format!("<t:{}:{}>", epoch, style),
+        ChatPlatform::Slack => format!("<!date^{}^{}|{}>", epoch, tokens, time.to_rfc3339()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use crate::chat::{ChatPlatform, format_chat_timestamp};
+
+    #[test]
+    fn discord_markup() {
+        let time = Utc.timestamp_opt(1752170474, 0).unwrap();
+        assert_eq!(format_chat_timestamp(time, "R", ChatPlatform::Discord), Ok("<t:1752170474:R>".to_string()));
+        assert_eq!(format_chat_timestamp(time, "F", ChatPlatform::Discord), Ok("<t:1752170474:F>".to_string()));
+    }
+
+    #[test]
+    fn slack_markup() {
+        let time = Utc.timestamp_opt(1752170474, 0).unwrap();
+        assert_eq!(
+            format_chat_timestamp(time, "R", ChatPlatform::Slack),
+            Ok("<!date^1752170474^{ago}|2025-07-10T18:01:14+00:00>".to_string())
+        );
+        assert_eq!(
+            format_chat_timestamp(time, "d", ChatPlatform::Slack),
+            Ok("<!date^1752170474^{date_num}|2025-07-10T18:01:14+00:00>".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_style() {
+        let time = Utc.timestamp_opt(0, 0).unwrap();
+        assert!(format_chat_timestamp(time, "x", ChatPlatform::Discord).is_err());
+        assert!("teams".parse::<ChatPlatform>().is_err());
+        assert_eq!("Slack".parse::<ChatPlatform>(), Ok(ChatPlatform::Slack));
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An opaque sRGB color, parsed from hex like "1e1e2e", "#1e1e2e" or the short form "#fff".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+pub const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+pub const WHITE: Rgb = Rgb { r: 255, g: 255, b: 255 };
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid hex color: {}", s));
+        }
+
+        // Short colors repeat each digit, so "f80" is "ff8800"
+        let width = hex.len() / 3;
+        let channel = |i: usize| {
+            let digits = &hex[i * width..(i + 1) * width];
+            u8::from_str_radix(&digits.repeat(3 - width), 16).map_err(|_| format!("Invalid hex color: {}", s))
+        };
+        Ok(Rgb { r: channel(0)?, g: channel(1)?, b: channel(2)? })
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl Rgb {
+    /// Relative luminance as defined by WCAG 2, from 0 (black) to 1 (white).
+    pub fn luminance(&self) -> f64 {
+        let linear = |channel: u8| {
+            let c = channel as f64 / 255.0;
+            if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    /// WCAG contrast ratio between two colors, from 1 (identical) to 21 (black on white).
+    pub fn contrast(&self, other: &Rgb) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// Black or white, whichever is more readable on top of this color.
+    pub fn contrasting_text(&self) -> Rgb {
+        if self.contrast(&WHITE) > self.contrast(&BLACK) { WHITE } else { BLACK }
+    }
+}
+
+/// A banner color scheme, filling in colors that weren't given explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Dark text on a transparent background.
+    #[default]
+    Light,
+    /// Light text on a dark background, for dark pages.
+    Dark,
+    /// Light or dark, following the viewer's `prefers-color-scheme`.
+    Auto,
+}
+
+/// The dark theme's background and text.
+pub const DARK_BACKGROUND: Rgb = Rgb { r: 0x0d, g: 0x11, b: 0x17 };
+pub const DARK_TEXT: Rgb = Rgb { r: 0xe6, g: 0xed, b: 0xf3 };
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            "auto" => Ok(Theme::Auto),
+            _ => Err(format!("Unknown theme (expected light, dark or auto): {}", s)),
+        }
+    }
+}
+
+impl Theme {
+    /// The background and text colors this theme always uses. Light banners keep the templates' own colors,
+    /// and automatic ones pick theirs in the viewer's browser.
+    pub fn colors(self) -> Option<(Rgb, Rgb)> {
+        match self {
+            Theme::Dark => Some((DARK_BACKGROUND, DARK_TEXT)),
+            Theme::Light | Theme::Auto => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{BLACK, DARK_BACKGROUND, Rgb, Theme, WHITE};
+
+    #[test]
+    fn parse_hex() {
+        assert_eq!("1e1e2e".parse::<Rgb>(), Ok(Rgb { r: 0x1e, g: 0x1e, b: 0x2e }));
+        assert_eq!("#FFFFFF".parse::<Rgb>(), Ok(WHITE));
+        assert!("12345g".parse::<Rgb>().is_err());
+        assert!("1234".parse::<Rgb>().is_err());
+        assert_eq!("#fff".parse::<Rgb>(), Ok(WHITE));
+        assert_eq!("f80".parse::<Rgb>(), Ok(Rgb { r: 0xff, g: 0x88, b: 0x00 }));
+        assert!("ff".parse::<Rgb>().is_err());
+        assert!("#ggg".parse::<Rgb>().is_err());
+        assert!("".parse::<Rgb>().is_err());
+        assert_eq!(Rgb { r: 0x1e, g: 0x1e, b: 0x2e }.to_string(), "#1e1e2e");
+    }
+
+    #[test]
+    fn luminance() {
+        assert_eq!(BLACK.luminance(), 0.0);
+        assert!((WHITE.luminance() - 1.0).abs() < 1e-9);
+        assert!((BLACK.contrast(&WHITE) - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dark_background_white_text() {
+        assert_eq!("222222".parse::<Rgb>().unwrap().contrasting_text(), WHITE);
+        assert_eq!("1e1e2e".parse::<Rgb>().unwrap().contrasting_text(), WHITE);
+        assert_eq!("0000ff".parse::<Rgb>().unwrap().contrasting_text(), WHITE);
+    }
+
+    #[test]
+    fn light_background_black_text() {
+        assert_eq!("f5f5f5".parse::<Rgb>().unwrap().contrasting_text(), BLACK);
+        assert_eq!("ffff00".parse::<Rgb>().unwrap().contrasting_text(), BLACK);
+        assert_eq!("f5a623".parse::<Rgb>().unwrap().contrasting_text(), BLACK);
+    }
+
+    #[test]
+    fn themes() {
+        assert_eq!("DARK".parse::<Theme>(), Ok(Theme::Dark));
+        assert_eq!("auto".parse::<Theme>(), Ok(Theme::Auto));
+        assert!("sepia".parse::<Theme>().is_err());
+        assert_eq!(Theme::Dark.colors().map(|(bg, _)| bg.to_string()), Some("#0d1117".to_string()));
+        assert_eq!(DARK_BACKGROUND.contrasting_text(), WHITE);
+        assert_eq!(Theme::Light.colors(), None);
+    }
+}
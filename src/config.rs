@@ -1,3 +1,5 @@
+use std::net::{IpAddr, Ipv4Addr};
+
 use serde::Deserialize;
 use tracing::Level;
 
@@ -8,6 +10,20 @@ pub enum Environment {
     Development,
 }
 
+/// How log lines are written.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log aggregation.
+    Json,
+    /// Multi-line, indented records.
+    Pretty,
+    /// Single lines without span context.
+    Compact,
+    /// tracing's standard single-line format.
+    Full,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Configuration {
     #[serde(default = "default_env")]
@@ -15,38 +31,154 @@ pub struct Configuration {
 
     #[serde(default = "default_port")]
     pub port: u16,
+
+    /// Address to listen on, overriding the environment's default (all interfaces in production, loopback in development).
+    #[serde(default)]
+    pub bind_addr: Option<IpAddr>,
+
+    /// Directory the SVG templates are loaded from, in place of the build's default.
+    #[serde(default)]
+    pub template_dir: Option<String>,
+
+    /// Directory bundled fonts are loaded from, in place of the build's default.
+    #[serde(default)]
+    pub font_dir: Option<String>,
+
+    /// Log line format; defaults to JSON in production and the standard format in development.
+    #[serde(default)]
+    pub log_format: Option<LogFormat>,
+
+    /// Seconds a request may take before it's answered with `408 Request Timeout`.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+
+    /// Maximum number of rendered banners kept in the render cache.
+    #[serde(default = "default_cache_capacity")]
+    pub cache_capacity: usize,
+
+    /// Seconds a rendered banner stays valid in the render cache.
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl: u64,
+
+    /// `max-age` (in seconds) sent with banners that change as time passes, like relative times.
+    #[serde(default = "default_cache_relative_maxage")]
+    pub cache_relative_maxage: u64,
+
+    /// `max-age` (in seconds) sent with banners of a fixed time, like absolute times. These are also marked immutable.
+    #[serde(default = "default_cache_absolute_maxage")]
+    pub cache_absolute_maxage: u64,
+
+    /// Enables diagnostic features (such as `?debug_timing=1`) that should stay off in production.
+    #[serde(default)]
+    pub debug_endpoints: bool,
+
+    /// Client networks (CIDRs or single addresses) that are refused with a 403.
+    #[serde(default)]
+    pub blocked_networks: Vec<String>,
+
+    /// Regular expressions matched against the request path; matching requests are refused with a 403.
+    #[serde(default)]
+    pub blocked_paths: Vec<String>,
+
+    /// Allows `?pixel=1`, which answers with a 1x1 transparent PNG and logs the viewer's IP and referrer.
+    #[serde(default)]
+    pub tracking_pixel: bool,
+
+    /// Timezones accepted by `?tz=`; any other zone is refused with a 400. Empty allows every zone.
+    #[serde(default)]
+    pub allowed_timezones: Vec<String>,
+
+    /// Timezone banners are shown in when `?tz=` isn't given, in place of UTC. Any zone `?tz=` accepts may be used.
+    #[serde(default)]
+    pub default_tz: Option<String>,
+
+    /// Segment order of numeric dates when `?order=` isn't given: `YMD` (the default), `MDY` or `DMY`.
+    #[serde(default)]
+    pub default_date_order: Option<String>,
+
+    /// Seconds between pre-renders of the current-minute relative banner. Zero disables pre-rendering.
+    #[serde(default)]
+    pub prerender_interval: u64,
+
+    /// Extensions the current-minute banner is pre-rendered in.
+    #[serde(default = "default_prerender_formats")]
+    pub prerender_formats: Vec<String>,
+
+    /// `Strict-Transport-Security` value added to every response, if set.
+    #[serde(default)]
+    pub hsts: Option<String>,
+
+    /// `Referrer-Policy` value added to every response, if set.
+    #[serde(default)]
+    pub referrer_policy: Option<String>,
+
+    /// Reject unrecognized extensions with a 400, instead of serving them as SVG.
+    #[serde(default)]
+    pub strict_extensions: bool,
+
+    /// Where to look for a request's output format, in order: `extension`, `prefer` and `default`. `accept` is opt-in,
+    /// since browsers list several image types at equal quality.
+    #[serde(default = "default_format_precedence")]
+    pub format_precedence: Vec<String>,
+}
+
+fn default_format_precedence() -> Vec<String> {
+    ["extension", "prefer", "default"].iter().map(|source| source.to_string()).collect()
 }
 
 fn default_port() -> u16 {
     3000
 }
 
+fn default_request_timeout() -> u64 {
+    10
+}
+
+fn default_cache_capacity() -> usize {
+    256
+}
+
+fn default_cache_ttl() -> u64 {
+    60
+}
+
+fn default_cache_relative_maxage() -> u64 {
+    60
+}
+
+fn default_cache_absolute_maxage() -> u64 {
+    31_536_000
+}
+
+fn default_prerender_formats() -> Vec<String> {
+    if cfg!(feature = "raster") {
+        vec!["svg".to_string(), "png".to_string()]
+    } else {
+        vec!["svg".to_string()]
+    }
+}
+
 fn default_env() -> Environment {
     Environment::Development
 }
 
 impl Configuration {
-    pub fn socket_addr(&self) -> [u8; 4] {
-        match self.env {
-            Environment::Production => {
-                let socket = [0, 0, 0, 0];
-                tracing::info!(
-                    "Starting Production on {:?}:{}",
-                    socket.as_slice(),
-                    self.port
-                );
-                socket
-            }
-            Environment::Development => {
-                let socket = [127, 0, 0, 1];
-                tracing::info!(
-                    "Starting Development on {:?}:{}",
-                    socket.as_slice(),
-                    self.port
-                );
-                socket
-            }
-        }
+    pub fn socket_addr(&self) -> IpAddr {
+        let (name, default) = match self.env {
+            Environment::Production => ("Production", Ipv4Addr::UNSPECIFIED),
+            Environment::Development => ("Development", Ipv4Addr::LOCALHOST),
+        };
+
+        let addr = self.bind_addr.unwrap_or(IpAddr::V4(default));
+        tracing::info!("Starting {} on {}:{}", name, addr, self.port);
+        addr
+    }
+
+    pub fn log_format(&self) -> LogFormat {
+        self.log_format.unwrap_or(match self.env {
+            Environment::Production => LogFormat::Json,
+            Environment::Development => LogFormat::Full,
+        })
     }
 
     pub fn log_level(&self) -> Level {
@@ -55,4 +187,54 @@ impl Configuration {
             Environment::Development => Level::DEBUG,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl Configuration {
+    /// Read a configuration from the given variables in place of the process environment.
+    pub fn from_vars(vars: Vec<(&str, &str)>) -> Result<Self, envy::Error> {
+        envy::from_iter(vars.into_iter().map(|(k, v)| (k.to_string(), v.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use crate::config::{Configuration, LogFormat};
+
+    #[test]
+    fn bind_addr_override() {
+        let production = Configuration::from_vars(vec![("ENV", "production"), ("BIND_ADDR", "10.0.0.5")]).unwrap();
+        assert_eq!(production.socket_addr(), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)));
+
+        let development = Configuration::from_vars(vec![("BIND_ADDR", "::1")]).unwrap();
+        assert_eq!(development.socket_addr(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+    }
+
+    #[test]
+    fn bind_addr_invalid() {
+        assert!(Configuration::from_vars(vec![("BIND_ADDR", "localhost")]).is_err());
+        assert!(Configuration::from_vars(vec![("BIND_ADDR", "256.0.0.1")]).is_err());
+    }
+
+    #[test]
+    fn bind_addr_default() {
+        let production = Configuration::from_vars(vec![("ENV", "production")]).unwrap();
+        assert_eq!(production.socket_addr(), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        let development = Configuration::from_vars(vec![]).unwrap();
+        assert_eq!(development.socket_addr(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+    }
+
+    #[test]
+    fn log_format() {
+        assert_eq!(Configuration::from_vars(vec![("LOG_FORMAT", "json")]).unwrap().log_format(), LogFormat::Json);
+        assert_eq!(Configuration::from_vars(vec![("LOG_FORMAT", "compact")]).unwrap().log_format(), LogFormat::Compact);
+        assert_eq!(Configuration::from_vars(vec![("ENV", "production"), ("LOG_FORMAT", "pretty")]).unwrap().log_format(), LogFormat::Pretty);
+        assert!(Configuration::from_vars(vec![("LOG_FORMAT", "xml")]).is_err());
+
+        assert_eq!(Configuration::from_vars(vec![("ENV", "production")]).unwrap().log_format(), LogFormat::Json);
+        assert_eq!(Configuration::from_vars(vec![]).unwrap().log_format(), LogFormat::Full);
+    }
+}
@@ -0,0 +1,187 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, TimeZone, Timelike, Utc};
+
+/// How far ahead to look for the next occurrence. Eight years covers the gap between leap days across a
+/// skipped century (like 2096 to 2104), so any valid schedule fires within it.
+const MAX_SEARCH_DAYS: i64 = 366 * 8;
+
+/// A standard five-field cron expression ("minute hour day-of-month month day-of-week"), evaluated in UTC.
+/// Each field is a set of values stored as a bitmask.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether each day field was given as something other than "*". When both are, a day matching either fires.
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+/// Parse one cron field: a comma separated list of "*", values or ranges, each optionally stepped ("*/15", "1-5/2").
+fn parse_field(raw: &str, name: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid {} field: {}", name, raw);
+    let value = |raw: &str| match raw.parse::<u32>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!("{} value '{}' is out of range {}-{}", name, raw, min, max)),
+    };
+
+    let mut mask = 0;
+    for part in raw.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // A stepped single value ("5/15") runs to the end of the field
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!("Cron expressions need five fields (minute hour day month weekday), got {}", fields.len()));
+        };
+
+        // Sunday is both 0 and 7
+        let mut days_of_week = parse_field(day_of_week, "day of week", 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(CronSchedule {
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days_of_month: parse_field(day_of_month, "day of month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            days_of_week,
+            day_of_month_restricted: day_of_month != "*",
+            day_of_week_restricted: day_of_week != "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day_of_month = self.days_of_month & (1 << date.day()) != 0;
+        let day_of_week = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+
+        self.months & (1 << date.month()) != 0 && match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    /// The first value in a field's mask at or after `from`.
+    fn first_from(mask: u64, from: u32) -> Option<u32> {
+        let remaining = mask >> from;
+        (remaining != 0).then(|| from + remaining.trailing_zeros())
+    }
+
+    /// The first time the schedule fires strictly after the given time, if it fires at all.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+
+        for offset in 0..MAX_SEARCH_DAYS {
+            let date = start.date_naive() + Duration::days(offset);
+            if !self.matches_day(date) {
+                continue;
+            }
+
+            // Only the first day is partially elapsed
+            let (from_hour, from_minute) = if offset == 0 { (start.hour(), start.minute()) } else { (0, 0) };
+            let mut hour = Self::first_from(self.hours, from_hour);
+            while let Some(current) = hour {
+                let minute_floor = if current == from_hour { from_minute } else { 0 };
+                if let Some(minute) = Self::first_from(self.minutes, minute_floor) {
+                    return Utc.from_local_datetime(&date.and_hms_opt(current, minute, 0)?).single();
+                }
+                hour = Self::first_from(self.hours, current + 1);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use crate::cron::CronSchedule;
+
+    fn next(expr: &str, year: i32, month: u32, day: u32, hour: u32, minute: u32) -> Option<chrono::DateTime<Utc>> {
+        let schedule: CronSchedule = expr.parse().unwrap();
+        schedule.next_after(Utc.with_ymd_and_hms(year, month, day, hour, minute, 30).unwrap())
+    }
+
+    #[test]
+    fn daily() {
+        // 2023-06-14 was a Wednesday
+        assert_eq!(next("0 9 * * *", 2023, 6, 14, 12, 0), Utc.with_ymd_and_hms(2023, 6, 15, 9, 0, 0).single());
+        assert_eq!(next("0 9 * * *", 2023, 6, 14, 8, 59), Utc.with_ymd_and_hms(2023, 6, 14, 9, 0, 0).single());
+        // Strictly after: the current minute has already fired
+        assert_eq!(next("0 9 * * *", 2023, 6, 14, 9, 0), Utc.with_ymd_and_hms(2023, 6, 15, 9, 0, 0).single());
+    }
+
+    #[test]
+    fn weekdays() {
+        // Friday afternoon waits for Monday morning
+        assert_eq!(next("0 9 * * 1-5", 2023, 6, 16, 12, 0), Utc.with_ymd_and_hms(2023, 6, 19, 9, 0, 0).single());
+        assert_eq!(next("0 9 * * 1-5", 2023, 6, 14, 12, 0), Utc.with_ymd_and_hms(2023, 6, 15, 9, 0, 0).single());
+        // Sunday may be written as 0 or 7
+        assert_eq!(next("30 18 * * 7", 2023, 6, 14, 12, 0), Utc.with_ymd_and_hms(2023, 6, 18, 18, 30, 0).single());
+        assert_eq!(next("30 18 * * 0", 2023, 6, 14, 12, 0), Utc.with_ymd_and_hms(2023, 6, 18, 18, 30, 0).single());
+    }
+
+    #[test]
+    fn steps_lists_and_days() {
+        assert_eq!(next("*/15 * * * *", 2023, 6, 14, 12, 7), Utc.with_ymd_and_hms(2023, 6, 14, 12, 15, 0).single());
+        assert_eq!(next("0 8,20 * * *", 2023, 6, 14, 12, 0), Utc.with_ymd_and_hms(2023, 6, 14, 20, 0, 0).single());
+        assert_eq!(next("0 0 1 * *", 2023, 12, 14, 12, 0), Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).single());
+        // With both day fields restricted, either one matching is enough: the 20th falls before the next Monday
+        assert_eq!(next("0 0 20 * 1", 2023, 6, 14, 12, 0), Utc.with_ymd_and_hms(2023, 6, 19, 0, 0, 0).single());
+        // Leap days are found years ahead
+        assert_eq!(next("0 0 29 2 *", 2097, 1, 1, 0, 0), Utc.with_ymd_and_hms(2104, 2, 29, 0, 0, 0).single());
+        // Days that never exist never fire
+        assert_eq!(next("0 0 31 2 *", 2023, 6, 14, 12, 0), None);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!("0 9 * *".parse::<CronSchedule>().is_err());
+        assert!("0 9 * * * *".parse::<CronSchedule>().is_err());
+        assert!("60 9 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 24 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 9 0 * *".parse::<CronSchedule>().is_err());
+        assert!("0 9 * 13 *".parse::<CronSchedule>().is_err());
+        assert!("0 9 * * 8".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("5-1 * * * *".parse::<CronSchedule>().is_err());
+        assert!("a * * * *".parse::<CronSchedule>().is_err());
+        assert_eq!(
+            "61 9 * * *".parse::<CronSchedule>(),
+            Err("minute value '61' is out of range 0-59".to_string())
+        );
+    }
+}
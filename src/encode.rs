@@ -0,0 +1,421 @@
+#[cfg(feature = "raster")]
+use axum::body::Bytes;
+#[cfg(feature = "raster")]
+use image_webp::{ColorType, WebPEncoder};
+#[cfg(feature = "raster")]
+use resvg::tiny_skia::Pixmap;
+
+use crate::color::Rgb;
+#[cfg(feature = "raster")]
+use crate::color::WHITE;
+#[cfg(feature = "raster")]
+use crate::raster::RenderError;
+
+/// JPEG quality used when none is requested.
+#[cfg(feature = "raster")]
+pub const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+/// Options shared by all encoders. Formats ignore the options that don't apply to them.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "raster"), allow(dead_code))]
+pub struct EncodeOptions {
+    /// Lossy compression quality from 1 to 100, defaulting to `DEFAULT_JPEG_QUALITY`.
+    pub quality: Option<u8>,
+    /// What transparent pixels are composited onto in formats without alpha, defaulting to white.
+    pub background: Option<Rgb>,
+    /// Composite onto the background in every format, so images with alpha come out opaque too.
+    pub opaque: bool,
+}
+
+impl EncodeOptions {
+    /// The color to fill an image with before drawing, if it should be opaque.
+    #[cfg(feature = "raster")]
+    pub fn fill(&self) -> Option<Rgb> {
+        self.opaque.then(|| self.background.unwrap_or(WHITE))
+    }
+}
+
+/// Parse a requested lossy quality; out of range values are clamped to 1 to 100.
+pub fn parse_quality(raw: &str) -> Result<u8, String> {
+    let quality = raw.parse::<i64>().map_err(|_| format!("Invalid quality: {}", raw))?;
+    Ok(quality.clamp(1, 100) as u8)
+}
+
+/// Encodes a rasterized pixmap into a specific image format.
+#[cfg(feature = "raster")]
+pub trait Encoder {
+    fn encode(&self, pixmap: &Pixmap, opts: &EncodeOptions) -> Result<Bytes, RenderError>;
+}
+
+#[cfg(feature = "raster")]
+pub struct PngEncoder;
+
+#[cfg(feature = "raster")]
+impl Encoder for PngEncoder {
+    fn encode(&self, pixmap: &Pixmap, _opts: &EncodeOptions) -> Result<Bytes, RenderError> {
+        pixmap
+            .encode_png()
+            .map(Bytes::from)
+            .map_err(|_| RenderError { message: Some("Failed to encode".to_string()) })
+    }
+}
+
+/// The pixmap's pixels as straight (not premultiplied) RGBA, which is what most encoders expect.
+#[cfg(feature = "raster")]
+fn demultiplied_rgba(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap.pixels().iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect()
+}
+
+/// Lossless WebP, which is usually much smaller than PNG for banners.
+#[cfg(feature = "raster")]
+pub struct WebpEncoder;
+
+#[cfg(feature = "raster")]
+impl Encoder for WebpEncoder {
+    fn encode(&self, pixmap: &Pixmap, _opts: &EncodeOptions) -> Result<Bytes, RenderError> {
+        let mut bytes = Vec::new();
+        WebPEncoder::new(&mut bytes)
+            .encode(&demultiplied_rgba(pixmap), pixmap.width(), pixmap.height(), ColorType::Rgba8)
+            .map_err(|e| RenderError { message: Some(format!("Failed to encode WebP: {}", e)) })?;
+
+        Ok(Bytes::from(bytes))
+    }
+}
+
+/// JPEG, for places that don't take PNG. JPEG has no transparency, so the banner is composited onto a solid background.
+#[cfg(feature = "raster")]
+pub struct JpegEncoder;
+
+#[cfg(feature = "raster")]
+impl Encoder for JpegEncoder {
+    fn encode(&self, pixmap: &Pixmap, opts: &EncodeOptions) -> Result<Bytes, RenderError> {
+        let background = opts.background.unwrap_or(WHITE);
+        // Pixels are premultiplied, so the background only has to fill in what the alpha leaves uncovered
+        let over = |channel: u8, background: u8, alpha: u8| channel + (background as u16 * (255 - alpha) as u16 / 255) as u8;
+        let rgb: Vec<u8> = pixmap.pixels().iter()
+            .flat_map(|pixel| [
+                over(pixel.red(), background.r, pixel.alpha()),
+                over(pixel.green(), background.g, pixel.alpha()),
+                over(pixel.blue(), background.b, pixel.alpha()),
+            ])
+            .collect();
+
+        let (width, height) = match (u16::try_from(pixmap.width()), u16::try_from(pixmap.height())) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(RenderError { message: Some("Image is too large for JPEG".to_string()) }),
+        };
+
+        let mut bytes = Vec::new();
+        jpeg_encoder::Encoder::new(&mut bytes, opts.quality.unwrap_or(DEFAULT_JPEG_QUALITY))
+            .encode(&rgb, width, height, jpeg_encoder::ColorType::Rgb)
+            .map_err(|e| RenderError { message: Some(format!("Failed to encode JPEG: {}", e)) })?;
+
+        Ok(Bytes::from(bytes))
+    }
+}
+
+/// GIF, as a single frame or (through `encode_frames` or `GifAnimation`) an animation.
+#[cfg(feature = "raster")]
+pub struct GifEncoder;
+
+#[cfg(feature = "raster")]
+impl GifEncoder {
+    /// Encode an endlessly looping animation, showing each frame for `delay` hundredths of a second.
+    /// Every frame must be the size of the first.
+    pub fn encode_frames(pixmaps: &[Pixmap], delay: u16) -> Result<Bytes, RenderError> {
+        let first = pixmaps.first().ok_or(RenderError { message: Some("No frames to encode".to_string()) })?;
+        let mut animation = GifAnimation::new(first.width(), first.height(), delay, pixmaps.len() > 1)?;
+        for pixmap in pixmaps {
+            animation.push(pixmap)?;
+        }
+
+        animation.finish()
+    }
+}
+
+/// A GIF encoded one frame at a time, so frames can be drawn and dropped in turn rather than all held at once.
+#[cfg(feature = "raster")]
+pub struct GifAnimation {
+    encoder: gif::Encoder<Vec<u8>>,
+    width: u16,
+    height: u16,
+    delay: u16,
+}
+
+#[cfg(feature = "raster")]
+impl GifAnimation {
+    /// Start a GIF of frames `width` by `height` pixels, each shown for `delay` hundredths of a second.
+    /// A looping GIF repeats endlessly.
+    pub fn new(width: u32, height: u32, delay: u16, looping: bool) -> Result<Self, RenderError> {
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(RenderError { message: Some("Image is too large for GIF".to_string()) }),
+        };
+
+        let mut encoder = gif::Encoder::new(Vec::new(), width, height, &[]).map_err(gif_error)?;
+        if looping {
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+        }
+
+        Ok(Self { encoder, width, height, delay })
+    }
+
+    /// Add a frame, which must be the size the GIF was started with.
+    pub fn push(&mut self, pixmap: &Pixmap) -> Result<(), RenderError> {
+        if (pixmap.width(), pixmap.height()) != (self.width as u32, self.height as u32) {
+            return Err(RenderError { message: Some("Every frame must be the same size".to_string()) });
+        }
+
+        let mut frame = gif::Frame::from_rgba_speed(self.width, self.height, &mut demultiplied_rgba(pixmap), 10);
+        frame.delay = self.delay;
+        // Frames are transparent, so each one has to clear the last instead of drawing over it
+        frame.dispose = gif::DisposalMethod::Background;
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+
+    /// Finish the GIF, returning its bytes.
+    pub fn finish(self) -> Result<Bytes, RenderError> {
+        self.encoder.into_inner().map(Bytes::from).map_err(|e| gif_error(e.into()))
+    }
+}
+
+#[cfg(feature = "raster")]
+fn gif_error(e: gif::EncodingError) -> RenderError {
+    RenderError { message: Some(format!("Failed to encode GIF: {}", e)) }
+}
+
+#[cfg(feature = "raster")]
+impl Encoder for GifEncoder {
+    fn encode(&self, pixmap: &Pixmap, _opts: &EncodeOptions) -> Result<Bytes, RenderError> {
+        GifEncoder::encode_frames(std::slice::from_ref(pixmap), 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Svg,
+    Png,
+    Webp,
+    Jpeg,
+    Gif,
+    /// The banner's text alone, without an image.
+    Text,
+    /// The banner's time and text as structured data.
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "svg" => Some(OutputFormat::Svg),
+            "png" => Some(OutputFormat::Png),
+            "webp" => Some(OutputFormat::Webp),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "gif" => Some(OutputFormat::Gif),
+            "txt" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// The format for a media type from an `Accept` header, including the standard `image/png`.
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type {
+            "image/svg+xml" => Some(OutputFormat::Svg),
+            "image/png" | "image/x-png" => Some(OutputFormat::Png),
+            "image/webp" => Some(OutputFormat::Webp),
+            "image/jpeg" => Some(OutputFormat::Jpeg),
+            "image/gif" => Some(OutputFormat::Gif),
+            "text/plain" => Some(OutputFormat::Text),
+            "application/json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "svg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Gif => "gif",
+            OutputFormat::Text => "txt",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "image/svg+xml",
+            OutputFormat::Png => "image/x-png",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Gif => "image/gif",
+            OutputFormat::Text => "text/plain; charset=utf-8",
+            OutputFormat::Json => "application/json",
+        }
+    }
+
+    /// Whether the format has to be rasterized, which needs the `raster` feature.
+    pub fn is_raster(&self) -> bool {
+        !matches!(self, OutputFormat::Svg | OutputFormat::Text | OutputFormat::Json)
+    }
+
+    /// Whether the format is made from the banner's text instead of the rendered SVG.
+    pub fn is_textual(&self) -> bool {
+        matches!(self, OutputFormat::Text | OutputFormat::Json)
+    }
+
+    /// The encoder for raster formats. Vector formats (SVG) are served as-is and have no encoder.
+    #[cfg(feature = "raster")]
+    pub fn encoder(&self) -> Option<Box<dyn Encoder>> {
+        match self {
+            OutputFormat::Svg | OutputFormat::Text | OutputFormat::Json => None,
+            OutputFormat::Png => Some(Box::new(PngEncoder)),
+            OutputFormat::Webp => Some(Box::new(WebpEncoder)),
+            OutputFormat::Jpeg => Some(Box::new(JpegEncoder)),
+            OutputFormat::Gif => Some(Box::new(GifEncoder)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encode::{OutputFormat, parse_quality};
+    #[cfg(feature = "raster")]
+    use resvg::tiny_skia::{Color, Pixmap};
+    #[cfg(feature = "raster")]
+    use crate::color::Rgb;
+    #[cfg(feature = "raster")]
+    use crate::encode::{EncodeOptions, Encoder, GifEncoder, JpegEncoder, PngEncoder, WebpEncoder};
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn png_encoder_output() {
+        let mut pixmap = Pixmap::new(12, 7).unwrap();
+        pixmap.fill(Color::from_rgba8(255, 0, 0, 255));
+
+        let bytes = PngEncoder.encode(&pixmap, &EncodeOptions::default()).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+
+        let reader = png::Decoder::new(bytes.as_ref()).read_info().unwrap();
+        assert_eq!(reader.info().width, 12);
+        assert_eq!(reader.info().height, 7);
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn webp_encoder_output() {
+        let mut pixmap = Pixmap::new(12, 7).unwrap();
+        pixmap.fill(Color::from_rgba8(255, 0, 0, 128));
+
+        let bytes = WebpEncoder.encode(&pixmap, &EncodeOptions::default()).unwrap();
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[8..12], b"WEBP");
+
+        // Lossless, so the translucent red comes back exactly
+        let mut decoder = image_webp::WebPDecoder::new(std::io::Cursor::new(bytes.as_ref())).unwrap();
+        assert_eq!(decoder.dimensions(), (12, 7));
+        let mut pixels = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut pixels).unwrap();
+        assert_eq!(&pixels[..4], &[255, 0, 0, 128]);
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn jpeg_encoder_output() {
+        let mut pixmap = Pixmap::new(16, 8).unwrap();
+        pixmap.fill(Color::from_rgba8(0, 0, 0, 0));
+
+        let bytes = JpegEncoder.encode(&pixmap, &EncodeOptions::default()).unwrap();
+        assert_eq!(&bytes[..3], b"\xff\xd8\xff");
+
+        // Transparent pixels become the background, white unless one is given
+        let decode = |bytes: &[u8]| jpeg_decoder::Decoder::new(bytes).decode().unwrap();
+        assert!(decode(&bytes).iter().all(|channel| *channel > 250));
+
+        let options = EncodeOptions { background: Some(Rgb { r: 0, g: 0, b: 0 }), ..Default::default() };
+        let bytes = JpegEncoder.encode(&pixmap, &options).unwrap();
+        assert!(decode(&bytes).iter().all(|channel| *channel < 5));
+
+        // Lower quality compresses a noisy image further
+        let mut noisy = Pixmap::new(64, 64).unwrap();
+        for (i, pixel) in noisy.pixels_mut().iter_mut().enumerate() {
+            let value = (i * 7919 % 251) as u8;
+            *pixel = resvg::tiny_skia::ColorU8::from_rgba(value, 255 - value, value / 2, 255).premultiply();
+        }
+        let size = |quality: u8| JpegEncoder.encode(&noisy, &EncodeOptions { quality: Some(quality), ..Default::default() }).unwrap().len();
+        assert!(size(10) < size(95));
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn gif_encoder_frames() {
+        let mut red = Pixmap::new(12, 7).unwrap();
+        red.fill(Color::from_rgba8(255, 0, 0, 255));
+        let mut blue = Pixmap::new(12, 7).unwrap();
+        blue.fill(Color::from_rgba8(0, 0, 255, 255));
+
+        let bytes = GifEncoder::encode_frames(&[red.clone(), blue], 100).unwrap();
+        assert_eq!(&bytes[..6], b"GIF89a");
+
+        let mut decoder = gif::DecodeOptions::new().read_info(bytes.as_ref()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (12, 7));
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 100);
+            frames += 1;
+        }
+        assert_eq!(frames, 2);
+
+        assert_eq!(&GifEncoder.encode(&red, &EncodeOptions::default()).unwrap()[..6], b"GIF89a");
+        assert!(GifEncoder::encode_frames(&[], 100).is_err());
+        assert!(GifEncoder::encode_frames(&[red, Pixmap::new(7, 12).unwrap()], 100).is_err());
+    }
+
+    #[test]
+    fn quality_clamped() {
+        assert_eq!(parse_quality("85"), Ok(85));
+        assert_eq!(parse_quality("0"), Ok(1));
+        assert_eq!(parse_quality("250"), Ok(100));
+        assert!(parse_quality("high").is_err());
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn format_encoders() {
+        assert!(OutputFormat::Svg.encoder().is_none());
+        assert!(OutputFormat::Png.encoder().is_some());
+        assert!(OutputFormat::Webp.encoder().is_some());
+        assert!(OutputFormat::Jpeg.encoder().is_some());
+        assert!(OutputFormat::Gif.encoder().is_some());
+    }
+
+    #[test]
+    fn format_extensions() {
+        assert!(!OutputFormat::Svg.is_raster());
+        assert!(OutputFormat::Png.is_raster());
+        assert_eq!(OutputFormat::from_extension("png"), Some(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_extension("gif"), Some(OutputFormat::Gif));
+        assert_eq!(OutputFormat::from_extension("bmp"), None);
+        assert_eq!(OutputFormat::from_extension("txt"), Some(OutputFormat::Text));
+        assert!(!OutputFormat::Text.is_raster() && OutputFormat::Text.is_textual());
+        assert_eq!(OutputFormat::from_mime_type("text/plain"), Some(OutputFormat::Text));
+        assert_eq!(OutputFormat::from_extension("json"), Some(OutputFormat::Json));
+        assert!(OutputFormat::Json.is_textual());
+        assert_eq!(OutputFormat::from_mime_type("image/png"), Some(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_mime_type(OutputFormat::Png.mime_type()), Some(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_mime_type("image/gif"), Some(OutputFormat::Gif));
+        assert_eq!(OutputFormat::from_mime_type("image/bmp"), None);
+        assert_eq!(OutputFormat::from_extension("webp"), Some(OutputFormat::Webp));
+        assert_eq!(OutputFormat::from_mime_type("image/webp"), Some(OutputFormat::Webp));
+        assert_eq!(OutputFormat::from_extension("jpeg"), Some(OutputFormat::Jpeg));
+        assert_eq!(OutputFormat::from_extension(OutputFormat::Jpeg.extension()), Some(OutputFormat::Jpeg));
+        assert_eq!(OutputFormat::Svg.extension(), "svg");
+    }
+}
@@ -2,11 +2,18 @@ use axum::http::{StatusCode};
 use axum::Json;
 use serde::{Serialize, Deserialize};
 
+#[derive(Debug, PartialEq)]
 pub enum TimeBannerError {
     ParseError(String),
+    /// A single component of a duration (like the minutes in "5h99999999999999m") could not be parsed.
+    DurationError { unit: &'static str, value: String },
     RenderError(String),
+    #[cfg(feature = "raster")]
     RasterizeError(String),
+    /// The requested output format can't be produced by this build.
+    NotAcceptable(String),
     NotFound,
+    Forbidden,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -15,13 +22,23 @@ pub struct ErrorResponse {
     message: String,
 }
 
-pub fn get_error_response(error: TimeBannerError) -> (StatusCode, Json<ErrorResponse>) {
-    let (code, message) = match error {
-        TimeBannerError::RenderError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, format!("RenderError :: {}", msg)),
-        TimeBannerError::ParseError(msg) => (StatusCode::BAD_REQUEST, format!("ParserError :: {}", msg)),
-        TimeBannerError::RasterizeError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, format!("RasterizeError :: {}", msg)),
-        TimeBannerError::NotFound => { (StatusCode::NOT_FOUND, "Not Found".to_string()) }
-    };
+impl TimeBannerError {
+    /// The HTTP status for this error, and a message describing it.
+    pub fn status_and_message(&self) -> (StatusCode, String) {
+        match self {
+            TimeBannerError::RenderError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, format!("RenderError :: {}", msg)),
+            TimeBannerError::ParseError(msg) => (StatusCode::BAD_REQUEST, format!("ParserError :: {}", msg)),
+            TimeBannerError::DurationError { unit, value } => (StatusCode::BAD_REQUEST, format!("DurationError :: Could not parse {} component '{}'", unit, value)),
+            #[cfg(feature = "raster")]
+            TimeBannerError::RasterizeError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, format!("RasterizeError :: {}", msg)),
+            TimeBannerError::NotAcceptable(msg) => (StatusCode::NOT_ACCEPTABLE, format!("NotAcceptable :: {}", msg)),
+            TimeBannerError::NotFound => { (StatusCode::NOT_FOUND, "Not Found".to_string()) }
+            TimeBannerError::Forbidden => { (StatusCode::FORBIDDEN, "Forbidden".to_string()) }
+        }
+    }
+}
 
+pub fn get_error_response(error: TimeBannerError) -> (StatusCode, Json<ErrorResponse>) {
+    let (code, message) = error.status_and_message();
     (code, Json(ErrorResponse { code: code.as_u16(), message }))
 }
\ No newline at end of file
@@ -0,0 +1,117 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::template::{format_iso_week, format_relative};
+
+/// Calendar metadata derived from a time, as seen in a specific offset.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TimeFields {
+    pub weekday: String,
+    pub day_of_year: u32,
+    pub iso_week: String,
+    pub is_leap_year: bool,
+    pub quarter: u32,
+    pub unix: i64,
+    pub iso: String,
+}
+
+impl TimeFields {
+    pub fn new(value: DateTime<Utc>, offset: FixedOffset) -> Self {
+        let local = value.with_timezone(&offset);
+
+        Self {
+            weekday: local.format("%A").to_string(),
+            day_of_year: local.ordinal(),
+            iso_week: format_iso_week(value, offset),
+            is_leap_year: NaiveDate::from_ymd_opt(local.year(), 2, 29).is_some(),
+            quarter: local.month0() / 3 + 1,
+            unix: value.timestamp(),
+            iso: local.to_rfc3339(),
+        }
+    }
+}
+
+/// Everything known about a banner's time, for the JSON output format.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TimeInfo {
+    pub epoch: i64,
+    /// The time in UTC, as RFC 3339.
+    pub utc: String,
+    /// The time from `now`, like "3 hours ago".
+    pub relative: String,
+    /// The timezone the banner is shown in, as requested.
+    pub timezone: String,
+    pub offset: String,
+    /// The text the banner would show.
+    pub text: String,
+    pub fields: TimeFields,
+}
+
+impl TimeInfo {
+    pub fn new(value: DateTime<Utc>, offset: FixedOffset, timezone: &str, text: String, now: DateTime<Utc>) -> Self {
+        Self {
+            epoch: value.timestamp(),
+            utc: value.to_rfc3339(),
+            relative: format_relative(value, now, false, false),
+            timezone: timezone.to_string(),
+            offset: offset.to_string(),
+            text,
+            fields: TimeFields::new(value, offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone, Utc};
+    use crate::fields::{TimeFields, TimeInfo};
+
+    #[test]
+    fn leap_day_fields() {
+        let value = Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap();
+        let fields = TimeFields::new(value, FixedOffset::east_opt(0).unwrap());
+
+        assert_eq!(fields, TimeFields {
+            weekday: "Thursday".to_string(),
+            day_of_year: 60,
+            iso_week: "2024-W09".to_string(),
+            is_leap_year: true,
+            quarter: 1,
+            unix: 1709208000,
+            iso: "2024-02-29T12:00:00+00:00".to_string(),
+        });
+    }
+
+    #[test]
+    fn offset_fields() {
+        // Late on New Year's Eve 2023 in UTC is already 2024 in UTC+9
+        let value = Utc.with_ymd_and_hms(2023, 12, 31, 20, 0, 0).unwrap();
+
+        let utc = TimeFields::new(value, FixedOffset::east_opt(0).unwrap());
+        assert_eq!(utc.weekday, "Sunday");
+        assert_eq!(utc.day_of_year, 365);
+        assert!(!utc.is_leap_year);
+        assert_eq!(utc.quarter, 4);
+
+        let tokyo = TimeFields::new(value, FixedOffset::east_opt(9 * 3600).unwrap());
+        assert_eq!(tokyo.weekday, "Monday");
+        assert_eq!(tokyo.day_of_year, 1);
+        assert!(tokyo.is_leap_year);
+        assert_eq!(tokyo.quarter, 1);
+        assert_eq!(tokyo.unix, utc.unix);
+        assert_eq!(tokyo.iso, "2024-01-01T05:00:00+09:00");
+    }
+
+    #[test]
+    fn time_info() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let offset = FixedOffset::east_opt(9 * 3600).unwrap();
+        let info = TimeInfo::new(value, offset, "JST", "banner".to_string(), value + chrono::Duration::hours(3));
+
+        assert_eq!(info.epoch, 1686744000);
+        assert_eq!(info.utc, "2023-06-14T12:00:00+00:00");
+        assert_eq!(info.relative, "3 hours ago");
+        assert_eq!((info.timezone.as_str(), info.offset.as_str()), ("JST", "+09:00"));
+        assert_eq!(info.fields.iso, "2023-06-14T21:00:00+09:00");
+    }
+}
@@ -0,0 +1,205 @@
+use axum::extract::State;
+use axum::http::{HeaderValue, Request, StatusCode};
+use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, VARY};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tower_http::compression::CompressionLayer;
+
+use crate::config::Configuration;
+use crate::state::AppState;
+
+/// Opt-in security headers asserted on every response, for operators that want the app itself to set them.
+pub struct SecurityHeaders {
+    hsts: Option<HeaderValue>,
+    referrer_policy: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    /// Build the header set from configuration, failing on any value that isn't a valid header.
+    pub fn from_config(config: &Configuration) -> Result<Self, String> {
+        let parse = |name: &str, value: &Option<String>| value.as_deref()
+            .map(|raw| HeaderValue::from_str(raw).map_err(|_| format!("Invalid {} header value: {}", name, raw)))
+            .transpose();
+
+        Ok(Self {
+            hsts: parse("Strict-Transport-Security", &config.hsts)?,
+            referrer_policy: parse("Referrer-Policy", &config.referrer_policy)?,
+        })
+    }
+}
+
+/// Middleware adding the configured security headers to responses.
+pub async fn security_headers_middleware<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    if let Some(hsts) = &state.security_headers.hsts {
+        headers.insert(STRICT_TRANSPORT_SECURITY, hsts.clone());
+    }
+    if let Some(referrer_policy) = &state.security_headers.referrer_policy {
+        headers.insert(REFERRER_POLICY, referrer_policy.clone());
+    }
+
+    response
+}
+
+/// Whether an `If-None-Match` value (a list of tags, or `*`) matches an `ETag`. Matching is weak, so `W/` is ignored.
+fn if_none_match(header: &str, etag: &str) -> bool {
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    header.trim() == "*" || header.split(',').any(|tag| strip_weak(tag) == strip_weak(etag))
+}
+
+/// Middleware answering `304 Not Modified` when the client already has the response, by its `ETag`.
+/// The caching headers are kept so the client can refresh its copy's lifetime.
+pub async fn conditional_middleware<B>(request: Request<B>, next: Next<B>) -> Response {
+    let condition = request.headers().get(IF_NONE_MATCH).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let response = next.run(request).await;
+
+    let (Some(condition), Some(etag)) = (condition, response.headers().get(ETAG)) else {
+        return response;
+    };
+    if response.status() != StatusCode::OK || !etag.to_str().is_ok_and(|etag| if_none_match(&condition, etag)) {
+        return response;
+    }
+
+    let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+    for name in [ETAG, CACHE_CONTROL, VARY] {
+        if let Some(value) = response.headers().get(&name) {
+            not_modified.headers_mut().insert(name, value.clone());
+        }
+    }
+
+    not_modified
+}
+
+/// Gzip or Brotli compression, as the client's `Accept-Encoding` allows.
+/// The default predicate skips images other than SVG, whose formats are already compressed, and tiny bodies.
+pub fn compression_layer() -> CompressionLayer {
+    CompressionLayer::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{middleware, Router, routing::get};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::http::header::{ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_NONE_MATCH, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY};
+    use tower::ServiceExt;
+    use crate::config::Configuration;
+    use crate::headers::{compression_layer, conditional_middleware, if_none_match, security_headers_middleware, SecurityHeaders};
+    use crate::routes::{absolute_handler, relative_handler};
+    use crate::state::AppState;
+
+    fn config(vars: Vec<(&str, &str)>) -> Configuration {
+        Configuration::from_vars(vars).unwrap()
+    }
+
+    fn app(vars: Vec<(&str, &str)>) -> Router {
+        let state = AppState::new(config(vars));
+        Router::new()
+            .route("/", get(|| async { "banner" }))
+            .layer(middleware::from_fn_with_state(state.clone(), security_headers_middleware))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn configured_headers() {
+        let response = app(vec![
+            ("HSTS", "max-age=31536000; includeSubDomains"),
+            ("REFERRER_POLICY", "no-referrer"),
+        ]).oneshot(Request::get("/").body(Body::empty()).unwrap()).await.unwrap();
+
+        assert_eq!(response.headers()[STRICT_TRANSPORT_SECURITY], "max-age=31536000; includeSubDomains");
+        assert_eq!(response.headers()[REFERRER_POLICY], "no-referrer");
+    }
+
+    #[tokio::test]
+    async fn individually_configured() {
+        let response = app(vec![("REFERRER_POLICY", "same-origin")])
+            .oneshot(Request::get("/").body(Body::empty()).unwrap()).await.unwrap();
+
+        assert!(response.headers().get(STRICT_TRANSPORT_SECURITY).is_none());
+        assert_eq!(response.headers()[REFERRER_POLICY], "same-origin");
+    }
+
+    #[test]
+    fn invalid_header_value() {
+        assert!(SecurityHeaders::from_config(&config(vec![("HSTS", "max-age=1\n")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn not_modified() {
+        let state = AppState::new(config(vec![]));
+        let app = Router::new()
+            .route("/absolute/:path", get(absolute_handler))
+            .route("/relative/:path", get(relative_handler))
+            .layer(middleware::from_fn(conditional_middleware))
+            .with_state(state);
+        let request_to = |uri: &str, etag: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(etag) = etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let request = |etag: Option<&str>| request_to("/absolute/1686744000.svg", etag);
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+
+        let response = app.clone().oneshot(request(Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        assert!(response.headers().contains_key(CACHE_CONTROL));
+        assert!(hyper::body::to_bytes(response.into_body()).await.unwrap().is_empty());
+
+        let response = app.clone().oneshot(request(Some("\"stale\""))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Relative banners are cached for the minute, so they keep matching within it
+        let response = app.clone().oneshot(request_to("/relative/1686744000.svg", None)).await.unwrap();
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+        let response = app.oneshot(request_to("/relative/1686744000.svg", Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[test]
+    fn if_none_match_lists() {
+        assert!(if_none_match("\"a\"", "\"a\""));
+        assert!(if_none_match("\"b\", W/\"a\"", "\"a\""));
+        assert!(if_none_match("*", "\"a\""));
+        assert!(!if_none_match("\"b\"", "\"a\""));
+    }
+
+    #[tokio::test]
+    async fn compressed_text() {
+        let app = Router::new()
+            .route("/absolute/:path", get(absolute_handler))
+            .layer(compression_layer())
+            .with_state(AppState::new(config(vec![])));
+        let request = |uri: &str, encoding: &str| Request::get(uri).header(ACCEPT_ENCODING, encoding).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(request("/absolute/1686744000.svg", "gzip")).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/svg+xml");
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+
+        let response = app.clone().oneshot(request("/absolute/1686744000.json", "br")).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "br");
+
+        let response = app.clone().oneshot(request("/absolute/1686744000.svg", "identity")).await.unwrap();
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+
+        // Raster formats are already compressed
+        #[cfg(feature = "raster")]
+        {
+            let response = app.oneshot(request("/absolute/1686744000.png", "gzip")).await.unwrap();
+            assert!(response.headers()[CONTENT_TYPE].to_str().unwrap().starts_with("image/"));
+            assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        }
+    }
+}
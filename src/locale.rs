@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use timeago::languages::{boxup, french::French, german::German, italian::Italian, portuguese::Portuguese, spanish::Spanish};
+use timeago::{BoxedLanguage, English, Language};
+
+/// A language relative times can be written in.
+/// timeago provides the words for past times; the phrasing of future and approximate times is kept here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    Spanish,
+    French,
+    German,
+    Italian,
+    Portuguese,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Parse a language code like "es", ignoring any region ("es-MX", "pt_BR").
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['-', '_']).next().unwrap_or(s);
+        match language.to_lowercase().as_str() {
+            "en" => Ok(Locale::English),
+            "es" => Ok(Locale::Spanish),
+            "fr" => Ok(Locale::French),
+            "de" => Ok(Locale::German),
+            "it" => Ok(Locale::Italian),
+            "pt" => Ok(Locale::Portuguese),
+            _ => Err(format!("Unsupported language: {}", s)),
+        }
+    }
+}
+
+impl Locale {
+    /// The timeago language for formatting durations.
+    pub fn language(self) -> BoxedLanguage {
+        match self {
+            Locale::English => boxup(English),
+            Locale::Spanish => boxup(Spanish),
+            Locale::French => boxup(French),
+            Locale::German => boxup(German),
+            Locale::Italian => boxup(Italian),
+            Locale::Portuguese => boxup(Portuguese),
+        }
+    }
+
+    /// Phrase a duration as lying in the past, like "3 days ago", with timeago's word for "ago".
+    pub fn past(self, duration: &str) -> String {
+        let language = self.language();
+        if language.place_ago_before() {
+            format!("{} {}", language.ago(), duration)
+        } else {
+            format!("{} {}", duration, language.ago())
+        }
+    }
+
+    /// Phrase a duration as lying in the future, like "in 3 days".
+    pub fn future(self, duration: &str) -> String {
+        let word = match self {
+            Locale::English | Locale::German => "in",
+            Locale::Spanish => "en",
+            Locale::French => "dans",
+            Locale::Italian => "tra",
+            Locale::Portuguese => "em",
+        };
+
+        format!("{} {}", word, duration)
+    }
+
+    /// Mark a duration as approximate, like "about 3 days".
+    pub fn about(self, duration: &str) -> String {
+        let word = match self {
+            Locale::English => "about",
+            Locale::Spanish | Locale::Portuguese => "cerca de",
+            Locale::French => "environ",
+            Locale::German => "etwa",
+            Locale::Italian => "circa",
+        };
+
+        format!("{} {}", word, duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::locale::Locale;
+
+    #[test]
+    fn language_codes() {
+        assert_eq!("es".parse(), Ok(Locale::Spanish));
+        assert_eq!("FR".parse(), Ok(Locale::French));
+        assert_eq!("pt-BR".parse(), Ok(Locale::Portuguese));
+        assert_eq!("de_AT".parse(), Ok(Locale::German));
+        assert!("xx".parse::<Locale>().is_err());
+        assert!("".parse::<Locale>().is_err());
+    }
+}
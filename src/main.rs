@@ -1,9 +1,20 @@
 use std::net::SocketAddr;
+use std::time::Duration;
 
-use axum::{Router, routing::get};
+use axum::{middleware, Router, routing::get};
 use dotenvy::dotenv;
-use config::Configuration;
-use crate::routes::{relative_handler, implicit_handler, absolute_handler, index_handler, fallback_handler};
+use tower_http::timeout::TimeoutLayer;
+use config::{Configuration, LogFormat};
+use state::AppState;
+use crate::blocklist::blocklist_middleware;
+use crate::headers::{compression_layer, conditional_middleware, security_headers_middleware};
+use crate::prerender::prerender_task;
+use crate::template::DEFAULT_TEMPLATE_DIR;
+#[cfg(feature = "raster")]
+use crate::pixel::pixel_middleware;
+#[cfg(feature = "raster")]
+use crate::routes::favicon_handler;
+use crate::routes::{relative_handler, implicit_handler, absolute_handler, index_handler, fallback_handler, epoch_handler, week_handler, digital_handler, clock_handler, chat_handler, fields_handler, liveness_handler, readiness_handler, metrics_handler, convert_handler, templates_handler, sparkline_handler, diff_handler, cron_handler, parse_handler, badge_handler, iso_handler};
 
 mod config;
 mod raster;
@@ -13,6 +24,22 @@ mod parse;
 mod template;
 mod error;
 mod relative;
+mod encode;
+mod cache;
+mod state;
+mod chat;
+mod blocklist;
+mod fields;
+mod headers;
+mod color;
+mod prerender;
+#[cfg(feature = "raster")]
+mod pixel;
+mod cron;
+mod negotiate;
+mod animate;
+mod locale;
+mod metrics;
 
 
 #[tokio::main]
@@ -24,21 +51,81 @@ async fn main() {
     let config = envy::from_env::<Configuration>().expect("Please provide PORT env var");
 
     // initialize tracing
-    tracing_subscriber::fmt()
+    let subscriber = tracing_subscriber::fmt()
         // With the log_level from our config
-        .with_max_level(config.log_level())
-        .init();
+        .with_max_level(config.log_level());
+    match config.log_format() {
+        LogFormat::Json => subscriber.json().init(),
+        LogFormat::Pretty => subscriber.pretty().init(),
+        LogFormat::Compact => subscriber.compact().init(),
+        LogFormat::Full => subscriber.init(),
+    }
 
     let app = Router::new()
         .route("/", get(index_handler))
+        .route("/healthz", get(liveness_handler))
+        .route("/readyz", get(readiness_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/templates", get(templates_handler))
+        .route("/sparkline", get(sparkline_handler))
+        .route("/diff/:a/:b", get(diff_handler))
+        .route("/cron", get(cron_handler))
+        .route("/parse", get(parse_handler))
         .route("/:path", get(implicit_handler))
         .route("/rel/:path", get(relative_handler))
         .route("/relative/:path", get(relative_handler))
         .route("/absolute/:path", get(absolute_handler))
         .route("/abs/:path", get(absolute_handler))
+        .route("/epoch/:path", get(epoch_handler))
+        .route("/week/:path", get(week_handler))
+        .route("/digital/:path", get(digital_handler))
+        .route("/clock/:path", get(clock_handler))
+        .route("/badge/:path", get(badge_handler))
+        .route("/iso/:path", get(iso_handler))
+        .route("/chat/:path", get(chat_handler))
+        .route("/fields/:path", get(fields_handler))
+        .route("/convert/:path", get(convert_handler))
         .fallback(fallback_handler);
+    #[cfg(feature = "raster")]
+    let app = app.route("/favicon.png", get(favicon_handler));
+
+    // Templates are loaded up front, so a broken template stops the server here instead of failing requests
+    let template_dir = config.template_dir.as_deref().unwrap_or(DEFAULT_TEMPLATE_DIR);
+    match template::init_templates(template_dir) {
+        Ok(count) => tracing::info!("Loaded {} templates from {}", count, template_dir),
+        Err(e) => panic!("{}", e),
+    }
+    // Fonts are loaded by the shared rasterizer, which the app state builds, so the directory must be set first
+    #[cfg(feature = "raster")]
+    if let Some(dir) = &config.font_dir {
+        raster::set_font_dir(dir);
+    }
+    #[cfg(not(feature = "raster"))]
+    if config.font_dir.is_some() {
+        tracing::warn!("FONT_DIR is ignored in builds without the raster feature");
+    }
 
     let addr = SocketAddr::from((config.socket_addr(), config.port));
+    let state = AppState::new(config);
+    if let Some(fonts) = state.font_count() {
+        tracing::info!("Loaded {} font faces", fonts);
+    }
+    if state.config.prerender_interval > 0 {
+        tokio::spawn(prerender_task(state.clone()));
+    }
+    #[cfg(feature = "raster")]
+    let app = app.layer(middleware::from_fn_with_state(state.clone(), pixel_middleware));
+    #[cfg(not(feature = "raster"))]
+    if state.config.tracking_pixel {
+        tracing::warn!("TRACKING_PIXEL is ignored in builds without the raster feature");
+    }
+    let app = app
+        .layer(middleware::from_fn(conditional_middleware))
+        .layer(middleware::from_fn_with_state(state.clone(), blocklist_middleware))
+        .layer(middleware::from_fn_with_state(state.clone(), security_headers_middleware))
+        .layer(compression_layer())
+        .layer(TimeoutLayer::new(Duration::from_secs(state.config.request_timeout)))
+        .with_state(state);
     axum::Server::bind(&addr)
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
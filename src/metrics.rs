@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the rasterization latency buckets, in seconds.
+const RASTERIZE_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Counters and latencies for `/metrics`, written in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    /// Requests handled, by route.
+    requests: Mutex<BTreeMap<&'static str, u64>>,
+    /// Rasterizations falling into each bucket, not cumulative; the last slot counts those beyond every bucket.
+    rasterize_buckets: [AtomicU64; RASTERIZE_BUCKETS.len() + 1],
+    rasterize_micros: AtomicU64,
+    rasterize_failures: AtomicU64,
+}
+
+impl Metrics {
+    /// Count a request to the given route.
+    pub fn record_request(&self, route: &'static str) {
+        let mut requests = self.requests.lock().unwrap();
+        *requests.entry(route).or_insert(0) += 1;
+    }
+
+    /// Record how long a rasterization took, and whether it failed.
+    pub fn record_rasterize(&self, elapsed: Duration, failed: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = RASTERIZE_BUCKETS.iter().position(|&bound| seconds <= bound).unwrap_or(RASTERIZE_BUCKETS.len());
+        self.rasterize_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.rasterize_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if failed {
+            self.rasterize_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Write every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP time_banner_requests_total Requests handled, by route.\n");
+        out.push_str("# TYPE time_banner_requests_total counter\n");
+        for (route, count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(out, "time_banner_requests_total{{route=\"{}\"}} {}", route, count);
+        }
+
+        out.push_str("# HELP time_banner_rasterize_seconds Time spent rasterizing and encoding banners.\n");
+        out.push_str("# TYPE time_banner_rasterize_seconds histogram\n");
+        let mut cumulative = 0;
+        for (bound, bucket) in RASTERIZE_BUCKETS.iter().zip(&self.rasterize_buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "time_banner_rasterize_seconds_bucket{{le=\"{}\"}} {}", bound, cumulative);
+        }
+        cumulative += self.rasterize_buckets[RASTERIZE_BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "time_banner_rasterize_seconds_bucket{{le=\"+Inf\"}} {}", cumulative);
+        let sum = self.rasterize_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "time_banner_rasterize_seconds_sum {}", sum);
+        let _ = writeln!(out, "time_banner_rasterize_seconds_count {}", cumulative);
+
+        out.push_str("# HELP time_banner_rasterize_failures_total Rasterizations that failed while drawing or encoding.\n");
+        out.push_str("# TYPE time_banner_rasterize_failures_total counter\n");
+        let _ = writeln!(out, "time_banner_rasterize_failures_total {}", self.rasterize_failures.load(Ordering::Relaxed));
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn exposition() {
+        let metrics = Metrics::default();
+        metrics.record_request("relative");
+        metrics.record_request("relative");
+        metrics.record_request("absolute");
+        metrics.record_rasterize(Duration::from_millis(3), false);
+        metrics.record_rasterize(Duration::from_secs(2), true);
+
+        let output = metrics.render();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.contains(&"time_banner_requests_total{route=\"relative\"} 2"));
+        assert!(lines.contains(&"time_banner_requests_total{route=\"absolute\"} 1"));
+        assert!(lines.contains(&"time_banner_rasterize_seconds_bucket{le=\"0.0025\"} 0"));
+        assert!(lines.contains(&"time_banner_rasterize_seconds_bucket{le=\"0.005\"} 1"));
+        assert!(lines.contains(&"time_banner_rasterize_seconds_bucket{le=\"1\"} 1"));
+        assert!(lines.contains(&"time_banner_rasterize_seconds_bucket{le=\"+Inf\"} 2"));
+        assert!(lines.contains(&"time_banner_rasterize_seconds_sum 2.003"));
+        assert!(lines.contains(&"time_banner_rasterize_seconds_count 2"));
+        assert!(lines.contains(&"time_banner_rasterize_failures_total 1"));
+    }
+}
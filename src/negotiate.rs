@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use crate::config::Configuration;
+use crate::encode::OutputFormat;
+
+/// A place the output format of a request can come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatSource {
+    /// The path's extension, like `.png`.
+    Extension,
+    /// The `Accept` header, like `image/png`.
+    Accept,
+    /// The `?prefer=` query parameter, like `?prefer=png`.
+    Prefer,
+    /// SVG, which always applies.
+    Default,
+}
+
+impl FromStr for FormatSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "extension" => Ok(FormatSource::Extension),
+            "accept" => Ok(FormatSource::Accept),
+            "prefer" => Ok(FormatSource::Prefer),
+            "default" => Ok(FormatSource::Default),
+            _ => Err(format!("Unknown format source: {}", s)),
+        }
+    }
+}
+
+/// The order in which format sources are consulted; the first one that names a format wins.
+pub struct FormatPrecedence {
+    sources: Vec<FormatSource>,
+}
+
+impl FormatPrecedence {
+    /// Build the precedence from `FORMAT_PRECEDENCE`, failing on unknown sources.
+    pub fn from_config(config: &Configuration) -> Result<Self, String> {
+        let sources = config.format_precedence.iter()
+            .map(|raw| raw.parse::<FormatSource>())
+            .collect::<Result<Vec<FormatSource>, String>>()?;
+
+        Ok(Self { sources })
+    }
+
+    /// Pick the extension to render a request as. Extensions and `?prefer=` are taken as given (so unknown ones
+    /// are still subject to `STRICT_EXTENSIONS`), while `Accept` only counts if it names a format we can produce.
+    /// Falls back to SVG if no source decides, even when `default` isn't listed.
+    pub fn negotiate<'a>(&self, extension: Option<&'a str>, accept: Option<&str>, prefer: Option<&'a str>) -> &'a str {
+        self.sources.iter()
+            .find_map(|source| match source {
+                FormatSource::Extension => extension,
+                FormatSource::Accept => accept.and_then(accepted_format).map(|format| format.extension()),
+                FormatSource::Prefer => prefer,
+                FormatSource::Default => Some(OutputFormat::Svg.extension()),
+            })
+            .unwrap_or(OutputFormat::Svg.extension())
+    }
+
+    /// Whether the `Accept` header is consulted before another source decides the format, in which case the response
+    /// differs by `Accept` and must be sent with `Vary: Accept`.
+    pub fn consults_accept(&self, extension: Option<&str>, prefer: Option<&str>) -> bool {
+        self.sources.iter()
+            .find_map(|source| match source {
+                FormatSource::Extension => extension.map(|_| false),
+                FormatSource::Accept => Some(true),
+                FormatSource::Prefer => prefer.map(|_| false),
+                FormatSource::Default => Some(false),
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// The most preferred format in an `Accept` header that we can produce, by quality value.
+/// Wildcards like `*/*` don't pick a format.
+fn accepted_format(accept: &str) -> Option<OutputFormat> {
+    accept.split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let format = OutputFormat::from_mime_type(parts.next()?.trim())?;
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (quality > 0.0).then_some((format, quality))
+        })
+        // Earlier entries win ties
+        .fold(None, |best: Option<(OutputFormat, f32)>, (format, quality)| match best {
+            Some((_, best_quality)) if best_quality >= quality => best,
+            _ => Some((format, quality)),
+        })
+        .map(|(format, _)| format)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Configuration;
+    use crate::encode::OutputFormat;
+    use crate::negotiate::{accepted_format, FormatPrecedence};
+
+    fn precedence(raw: &str) -> FormatPrecedence {
+        let config = Configuration::from_vars(vec![("FORMAT_PRECEDENCE", raw)]).unwrap();
+        FormatPrecedence::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn default_precedence() {
+        let config = Configuration::from_vars(vec![]).unwrap();
+        let precedence = FormatPrecedence::from_config(&config).unwrap();
+
+        // The extension wins over ?prefer=, and the Accept header isn't consulted
+        assert_eq!(precedence.negotiate(Some("svg"), Some("image/png"), Some("png")), "svg");
+        assert_eq!(precedence.negotiate(None, Some("image/png"), Some("png")), "png");
+        assert_eq!(precedence.negotiate(None, None, None), "svg");
+        // Chrome's Accept header for images lists WebP before SVG at the same quality
+        assert_eq!(precedence.negotiate(None, Some("image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8"), None), "svg");
+        assert!(!precedence.consults_accept(None, None));
+    }
+
+    #[test]
+    fn accept_consulted() {
+        let precedence = precedence("extension,accept,prefer,default");
+        assert_eq!(precedence.negotiate(None, Some("image/png"), Some("svg")), "png");
+        assert_eq!(precedence.negotiate(None, Some("*/*"), Some("png")), "png");
+        assert!(precedence.consults_accept(None, Some("png")));
+        assert!(!precedence.consults_accept(Some("svg"), None));
+
+        assert!(!self::precedence("prefer,accept,default").consults_accept(None, Some("png")));
+        assert!(self::precedence("prefer,accept,default").consults_accept(None, None));
+    }
+
+    #[test]
+    fn accept_over_extension() {
+        let precedence = precedence("accept,extension,default");
+        assert_eq!(precedence.negotiate(Some("svg"), Some("image/png"), None), "png");
+        assert_eq!(precedence.negotiate(Some("svg"), Some("text/html"), None), "svg");
+        assert_eq!(precedence.negotiate(None, None, Some("png")), "svg");
+    }
+
+    #[test]
+    fn invalid_precedence() {
+        let config = Configuration::from_vars(vec![("FORMAT_PRECEDENCE", "extension,header")]).unwrap();
+        assert!(FormatPrecedence::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn accept_quality() {
+        assert_eq!(accepted_format("image/png"), Some(OutputFormat::Png));
+        assert_eq!(accepted_format("image/png;q=0.5, image/svg+xml"), Some(OutputFormat::Svg));
+        assert_eq!(accepted_format("image/svg+xml;q=0.8, image/png;q=0.9"), Some(OutputFormat::Png));
+        assert_eq!(accepted_format("image/svg+xml, image/png"), Some(OutputFormat::Svg));
+        assert_eq!(accepted_format("image/png;q=0"), None);
+        assert_eq!(accepted_format("text/html, */*;q=0.8"), None);
+    }
+}
@@ -1,21 +1,211 @@
-use chrono::{DateTime, FixedOffset, Utc};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::abbr::parse_abbreviation;
+use crate::error::TimeBannerError;
+use crate::relative::{parse_time_value_with, ParseOptions};
+
+lazy_static! {
+    // A fixed UTC offset, with optional minutes: "+05:30", "-0600" or "+09"
+    static ref FIXED_OFFSET: Regex = Regex::new(r"^(?<sign>[+-])(?<hours>\d{2}):?(?<minutes>[0-5]\d)?$").unwrap();
+
+    // A date (in any segment order, see `DateSegmentOrder`), an optional time and an optional timezone abbreviation,
+    // like "2023-06-14-15:30-CST". The time may be on a 12-hour clock, like "3:30PM"
+    static ref ABSOLUTE_TIME: Regex = Regex::new(concat!(
+        r"^(?<date1>\d{1,4})[-./](?<date2>\d{1,2})[-./](?<date3>\d{1,4})",
+        r"(?:[-T ](?<hour>\d{1,2})(?::(?<minute>\d{2}))?(?::(?<second>\d{2}))?\s?(?<meridiem>[AaPp][Mm])?)?",
+        r"(?:-(?<timezone>[A-Za-z]+))?$")).unwrap();
+
+    // A date with the month written out, in any of "14 June 2023", "June 14, 2023" or "2023 Jun 14", then anything else
+    static ref NAMED_MONTH_DATE: Regex = Regex::new(concat!(
+        r"^(?:(?<dmy_day>\d{1,2})\s+(?<dmy_month>[A-Za-z]+)\.?,?\s+(?<dmy_year>\d{4})",
+        r"|(?<mdy_month>[A-Za-z]+)\.?\s+(?<mdy_day>\d{1,2}),?\s+(?<mdy_year>\d{4})",
+        r"|(?<ymd_year>\d{4})\s+(?<ymd_month>[A-Za-z]+)\.?\s+(?<ymd_day>\d{1,2}))",
+        r"(?<rest>.*)$")).unwrap();
+}
+
+/// The order of the numeric segments in a date: "2023-06-14" (YMD), "06-14-2023" (MDY) or "14-06-2023" (DMY).
+/// Years are always four digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateSegmentOrder {
+    #[default]
+    YearMonthDay,
+    MonthDayYear,
+    DayMonthYear,
+}
+
+impl FromStr for DateSegmentOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "YMD" => Ok(DateSegmentOrder::YearMonthDay),
+            "MDY" => Ok(DateSegmentOrder::MonthDayYear),
+            "DMY" => Ok(DateSegmentOrder::DayMonthYear),
+            _ => Err(format!("Unknown date order (expected YMD, MDY or DMY): {}", s)),
+        }
+    }
+}
+
+/// The number of an English month, given its full name or three-letter abbreviation (case-insensitive).
+fn month_number(name: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january", "february", "march", "april", "may", "june",
+        "july", "august", "september", "october", "november", "december",
+    ];
+    let name = name.to_lowercase();
+
+    MONTHS.iter()
+        .position(|month| *month == name || (name.len() == 3 && month.starts_with(&name)) || (name == "sept" && *month == "september"))
+        .map(|index| index as u32 + 1)
+}
+
+/// Rewrite a date with a named month ("14 June 2023") into the numeric, year-first form ("2023-06-14").
+/// The named month decides which number is the month, so the day and year can't be confused.
+/// Anything that isn't such a date is returned unchanged.
+fn normalize_named_month(raw: &str) -> Cow<'_, str> {
+    let Some(capture) = NAMED_MONTH_DATE.captures(raw) else {
+        return Cow::Borrowed(raw);
+    };
+
+    let fields = ["dmy", "mdy", "ymd"].iter().find_map(|order| Some((
+        capture.name(&format!("{}_day", order))?,
+        month_number(capture.name(&format!("{}_month", order))?.as_str()),
+        capture.name(&format!("{}_year", order))?,
+    )));
+    let Some((day, Some(month), year)) = fields else {
+        return Cow::Borrowed(raw);
+    };
+
+    let rest = capture.name("rest").map_or("", |rest| rest.as_str());
+    Cow::Owned(format!("{}-{:02}-{}{}", year.as_str(), month, day.as_str(), rest))
+}
+
+/// The components of an absolute time, as written by the user.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExtractedTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Timezone abbreviation the time was written in, UTC if not specified.
+    pub timezone: Option<String>,
+}
+
+impl ExtractedTime {
+    /// Check that every field is within its range, naming the first one that isn't.
+    /// Days are also checked against the length of their month, so February 30th is rejected.
+    pub fn validate(&self) -> Result<NaiveDateTime, TimeBannerError> {
+        let fields = [
+            ("month", self.month, 1..=12),
+            ("day", self.day, 1..=31),
+            ("hour", self.hour, 0..=23),
+            ("minute", self.minute, 0..=59),
+            ("second", self.second, 0..=59),
+        ];
+        for (field, value, range) in fields {
+            if !range.contains(&value) {
+                return Err(TimeBannerError::ParseError(format!("Invalid {}: {} (must be {} to {})", field, value, range.start(), range.end())));
+            }
+        }
+
+        NaiveDate::from_ymd_opt(self.year, self.month, self.day)
+            .and_then(|date| date.and_hms_opt(self.hour, self.minute, self.second))
+            .ok_or_else(|| TimeBannerError::ParseError(format!("Invalid day: {} is past the end of {}-{:02}", self.day, self.year, self.month)))
+    }
+
+    /// Resolve the wall-clock time in its timezone into a UTC instant, after validating its fields.
+    pub fn to_utc(&self) -> Result<DateTime<Utc>, TimeBannerError> {
+        let naive = self.validate()?;
+
+        let offset = match &self.timezone {
+            Some(abbreviation) => parse_abbreviation(abbreviation).map_err(TimeBannerError::ParseError)?,
+            None => FixedOffset::east_opt(0).unwrap(),
+        };
+
+        offset.from_local_datetime(&naive)
+            .single()
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .ok_or(TimeBannerError::ParseError("Invalid date or time".to_string()))
+    }
+}
+
+/// Convert an hour on a 12-hour clock to a 24-hour clock: 12AM is midnight and 12PM is noon.
+/// Returns None for hours that don't exist on a 12-hour clock (0 or above 12).
+fn to_24_hour(hour: u32, meridiem: &str) -> Option<u32> {
+    let pm = meridiem.eq_ignore_ascii_case("pm");
+    match hour {
+        12 => Some(if pm { 12 } else { 0 }),
+        1..=11 => Some(if pm { hour + 12 } else { hour }),
+        _ => None,
+    }
+}
+
+/// Extract the components of an absolute time like "2023-06-14-15-CST", "2023-06-14-3PM-CST" or "14 June 2023 15:00-CST".
+/// Numeric dates are read year first; see `extract_absolute_with` for other orders.
+/// Returns None if the input is not shaped like an absolute time, including 12-hour times like "15PM".
+#[allow(dead_code)]
+pub fn extract_absolute(raw: &str) -> Option<ExtractedTime> {
+    extract_absolute_with(raw, DateSegmentOrder::default())
+}
+
+/// Extract the components of an absolute time like `extract_absolute`, reading numeric dates in the given order.
+/// Dates with a named month are unambiguous, so they ignore the order.
+pub fn extract_absolute_with(raw: &str, order: DateSegmentOrder) -> Option<ExtractedTime> {
+    let normalized = normalize_named_month(raw);
+    let order = if matches!(normalized, Cow::Owned(_)) { DateSegmentOrder::YearMonthDay } else { order };
+    let capture = ABSOLUTE_TIME.captures(&normalized)?;
+    // The pattern only captures one to four digits per field, so parsing can't fail; missing fields are 0
+    let number = |name: &str| capture.name(name).map_or(0, |value| value.as_str().parse().unwrap_or(0));
+    let hour = match capture.name("meridiem") {
+        Some(meridiem) => to_24_hour(number("hour"), meridiem.as_str())?,
+        None => number("hour"),
+    };
+
+    let (year, month, day) = match order {
+        DateSegmentOrder::YearMonthDay => ("date1", "date2", "date3"),
+        DateSegmentOrder::MonthDayYear => ("date3", "date1", "date2"),
+        DateSegmentOrder::DayMonthYear => ("date3", "date2", "date1"),
+    };
+    // Only the year may be (and must be) four digits
+    if capture.name(year)?.as_str().len() != 4 || [month, day].iter().any(|name| capture[*name].len() > 2) {
+        return None;
+    }
+
+    Some(ExtractedTime {
+        year: number(year) as i32,
+        month: number(month),
+        day: number(day),
+        hour,
+        minute: number("minute"),
+        second: number("second"),
+        timezone: capture.name("timezone").map(|value| value.as_str().to_string()),
+    })
+}
 
 /// Split a path into a tuple of the preceding path and the extension.
 /// Can handle paths with multiple dots (period characters).
 /// Returns None if there is no extension.
 /// Returns None if the preceding path is empty (for example, dotfiles like ".env").
 pub fn split_on_extension(path: &str) -> Option<(&str, &str)> {
-    let split = path.rsplit_once('.');
-    if split.is_none() { return None; }
+    let split = path.rsplit_once('.')?;
 
     // Check that the file is not a dotfile (.env)
-    if split.unwrap().0.len() == 0 {
+    if split.0.is_empty() {
         return None;
     }
 
-    Some(split.unwrap())
+    Some(split)
 }
 
+#[allow(dead_code)]
 pub fn parse_absolute(raw: String) -> Result<(DateTime<Utc>, FixedOffset), String> {
     let datetime_with_offset = DateTime::parse_from_rfc3339(&raw);
     if datetime_with_offset.is_err() {
@@ -23,4 +213,333 @@ pub fn parse_absolute(raw: String) -> Result<(DateTime<Utc>, FixedOffset), Strin
     }
 
     Ok((datetime_with_offset.unwrap().with_timezone(&Utc), *(datetime_with_offset.unwrap().offset())))
-}
\ No newline at end of file
+}
+
+/// Parse a fixed UTC offset like "+05:30", "-0600" or "+09".
+fn parse_fixed_offset(raw: &str) -> Option<FixedOffset> {
+    let capture = FIXED_OFFSET.captures(raw)?;
+    let hours: i32 = capture["hours"].parse().ok()?;
+    let minutes: i32 = capture.name("minutes").map_or(Some(0), |minutes| minutes.as_str().parse().ok())?;
+    let seconds = hours * 3600 + minutes * 60;
+
+    match &capture["sign"] {
+        "-" => FixedOffset::west_opt(seconds),
+        _ => FixedOffset::east_opt(seconds),
+    }
+}
+
+/// Parse a timezone given by the user (via the `tz` query parameter) into a UTC offset.
+/// Timezone abbreviations (see `abbr_tz`) and fixed offsets like "+05:30" are supported.
+pub fn parse_timezone(raw: &str) -> Result<FixedOffset, String> {
+    parse_fixed_offset(raw).map_or_else(|| parse_abbreviation(raw), Ok)
+}
+
+/// Parse a timezone like `parse_timezone`, also accepting IANA zone names like "America/Chicago".
+/// Zones with daylight saving time have different offsets through the year, so the offset is the one in effect at `at`.
+pub fn parse_timezone_at(raw: &str, at: DateTime<Utc>) -> Result<FixedOffset, String> {
+    parse_timezone(raw).or_else(|e| match raw.parse::<Tz>() {
+        Ok(zone) => Ok(zone.offset_from_utc_datetime(&at.naive_utc()).fix()),
+        Err(_) => Err(e),
+    })
+}
+
+/// Whether a timezone is in the operator's allowlist. An empty allowlist leaves every zone available.
+pub fn is_timezone_allowed(raw: &str, allowed: &[String]) -> bool {
+    allowed.is_empty() || allowed.iter().any(|zone| zone.trim().eq_ignore_ascii_case(raw))
+}
+
+/// Parse a timezone like `parse_timezone_at`, but only accept zones in the operator's allowlist.
+pub fn parse_allowed_timezone(raw: &str, allowed: &[String], at: DateTime<Utc>) -> Result<FixedOffset, String> {
+    if !is_timezone_allowed(raw, allowed) {
+        return Err(format!("Timezone is not allowed: {}", raw));
+    }
+
+    parse_timezone_at(raw, at)
+}
+
+/// Resolve a wall-clock time in a timezone to an instant. IANA zones go through their own rules rather than a single
+/// offset, so wall-clock times that a daylight saving change repeats or skips are rejected as ambiguous or nonexistent.
+pub fn resolve_local_time(raw: &str, naive: &NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    let local = match parse_timezone(raw) {
+        Ok(offset) => offset.from_local_datetime(naive).map(|datetime| datetime.with_timezone(&Utc)),
+        Err(e) => raw.parse::<Tz>().map_err(|_| e)?.from_local_datetime(naive).map(|datetime| datetime.with_timezone(&Utc)),
+    };
+
+    match local {
+        LocalResult::Single(instant) => Ok(instant),
+        LocalResult::Ambiguous(_, _) => Err(format!("{} is ambiguous in {}", naive, raw)),
+        LocalResult::None => Err(format!("{} does not exist in {}", naive, raw)),
+    }
+}
+
+/// Interpret a time as being in the `from` zone and convert it to the `to` zone (both default to UTC).
+/// The `from` zone only applies to wall-clock absolute times; epochs and durations are already instants. Both zones
+/// must be in the operator's allowlist.
+pub fn convert_time(raw: &str, from: Option<&str>, to: Option<&str>, allowed: &[String], order: DateSegmentOrder) -> Result<DateTime<FixedOffset>, TimeBannerError> {
+    let invalid = |which: &str, e: String| TimeBannerError::ParseError(format!("Invalid '{}' timezone: {}", which, e));
+    if let Some(to) = to {
+        parse_allowed_timezone(to, allowed, Utc::now()).map_err(|e| invalid("to", e))?;
+    }
+    if let Some(from) = from {
+        parse_allowed_timezone(from, allowed, Utc::now()).map_err(|e| invalid("from", e))?;
+    }
+
+    let instant = match (extract_absolute_with(raw, order), from) {
+        (Some(extracted), Some(_)) if extracted.timezone.is_some() => {
+            return Err(TimeBannerError::ParseError("Timezone given both in the time and as 'from'".to_string()));
+        }
+        (Some(extracted), Some(from)) => resolve_local_time(from, &extracted.validate()?).map_err(TimeBannerError::ParseError)?,
+        (Some(extracted), None) => extracted.to_utc()?,
+        (None, _) => parse_time_value_with(raw, ParseOptions { order, ..Default::default() })?,
+    };
+
+    let to_offset = match to {
+        Some(to) => parse_allowed_timezone(to, allowed, instant).map_err(|e| invalid("to", e))?,
+        None => FixedOffset::east_opt(0).unwrap(),
+    };
+    Ok(instant.with_timezone(&to_offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone, Utc};
+    use crate::error::TimeBannerError;
+    use crate::parse::{convert_time, DateSegmentOrder, extract_absolute, extract_absolute_with, ExtractedTime, parse_allowed_timezone, parse_timezone, parse_timezone_at};
+
+    #[test]
+    fn extract_with_timezone() {
+        assert_eq!(extract_absolute("2023-06-14-15-CST"), Some(ExtractedTime {
+            year: 2023,
+            month: 6,
+            day: 14,
+            hour: 15,
+            minute: 0,
+            second: 0,
+            timezone: Some("CST".to_string()),
+        }));
+        assert_eq!(extract_absolute("1752170474"), None);
+        assert_eq!(extract_absolute("+1h"), None);
+    }
+
+    #[test]
+    fn extract_meridiem() {
+        let hour = |raw: &str| extract_absolute(raw).map(|extracted| extracted.hour);
+
+        assert_eq!(hour("2023-06-14-12AM"), Some(0));
+        assert_eq!(hour("2023-06-14-12PM"), Some(12));
+        assert_eq!(hour("2023-06-14-3PM"), Some(15));
+        assert_eq!(hour("2023-06-14-3am"), Some(3));
+        assert_eq!(hour("2023-06-14-15"), Some(15));
+        assert_eq!(hour("2023-06-14 3:30 PM"), Some(15));
+
+        let extracted = extract_absolute("2023-06-14-3:30PM-CST").unwrap();
+        assert_eq!((extracted.hour, extracted.minute), (15, 30));
+        assert_eq!(extracted.timezone.as_deref(), Some("CST"));
+
+        // Hours that don't exist on a 12-hour clock
+        assert_eq!(hour("2023-06-14-15PM"), None);
+        assert_eq!(hour("2023-06-14-0AM"), None);
+    }
+
+    #[test]
+    fn extract_named_months() {
+        let date = |raw: &str| extract_absolute(raw).map(|extracted| (extracted.year, extracted.month, extracted.day));
+
+        assert_eq!(date("14 June 2023"), Some((2023, 6, 14)));
+        assert_eq!(date("June 14 2023"), Some((2023, 6, 14)));
+        assert_eq!(date("June 14, 2023"), Some((2023, 6, 14)));
+        assert_eq!(date("2023 Jun 14"), Some((2023, 6, 14)));
+        assert_eq!(date("1 sept 2023"), Some((2023, 9, 1)));
+        assert_eq!(date("3 Dec. 2023"), Some((2023, 12, 3)));
+
+        // The time and timezone still follow the date
+        let extracted = extract_absolute("14 June 2023 3PM-CST").unwrap();
+        assert_eq!((extracted.month, extracted.hour), (6, 15));
+        assert_eq!(extracted.timezone.as_deref(), Some("CST"));
+
+        // Words that aren't months are left alone
+        assert_eq!(date("14 Juno 2023"), None);
+        assert_eq!(date("14 Ju 2023"), None);
+        assert!(extract_absolute("30 February 2023").unwrap().to_utc().is_err());
+    }
+
+    #[test]
+    fn parse_iana_zones() {
+        // New York is on EDT (UTC-4) in summer and EST (UTC-5) in winter
+        let summer = Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap();
+        let winter = Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(parse_timezone_at("America/New_York", summer), Ok(FixedOffset::west_opt(4 * 3600).unwrap()));
+        assert_eq!(parse_timezone_at("America/New_York", winter), Ok(FixedOffset::west_opt(5 * 3600).unwrap()));
+        assert_eq!(parse_timezone_at("Asia/Kolkata", winter), Ok(FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap()));
+        // Abbreviations keep their fixed offsets, even those that are also IANA names
+        assert_eq!(parse_timezone_at("EST", summer), Ok(FixedOffset::west_opt(5 * 3600).unwrap()));
+        assert!(parse_timezone_at("America/Nowhere", summer).is_err());
+
+        let converted = convert_time("1688212800", None, Some("America/New_York"), &[], DateSegmentOrder::default()).unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-07-01T08:00:00-04:00");
+    }
+
+    #[test]
+    fn parse_fixed_offsets() {
+        assert_eq!(parse_timezone("+05:30"), Ok(FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap()));
+        assert_eq!(parse_timezone("-0600"), Ok(FixedOffset::west_opt(6 * 3600).unwrap()));
+        assert_eq!(parse_timezone("+09"), Ok(FixedOffset::east_opt(9 * 3600).unwrap()));
+        assert_eq!(parse_timezone("cst"), Ok(FixedOffset::west_opt(6 * 3600).unwrap()));
+        // Offsets must be shorter than a day
+        assert!(parse_timezone("+24:00").is_err());
+        assert!(parse_timezone("+5").is_err());
+        assert!(parse_timezone("+05:60").is_err());
+    }
+
+    #[test]
+    fn extract_segment_orders() {
+        let date = |raw: &str, order: &str| extract_absolute_with(raw, order.parse().unwrap())
+            .map(|extracted| (extracted.year, extracted.month, extracted.day));
+
+        assert_eq!(date("2023-06-14", "YMD"), Some((2023, 6, 14)));
+        assert_eq!(date("06-14-2023", "MDY"), Some((2023, 6, 14)));
+        assert_eq!(date("14.06.2023-15-CST", "dmy"), Some((2023, 6, 14)));
+        // The year has to be where the order puts it
+        assert_eq!(date("06-14-2023", "YMD"), None);
+        assert_eq!(date("2023-06-14", "MDY"), None);
+        assert_eq!(date("0614-06-2023", "DMY"), None);
+        // Named months don't depend on the order
+        assert_eq!(date("14 June 2023", "MDY"), Some((2023, 6, 14)));
+
+        assert_eq!("mdy".parse::<DateSegmentOrder>(), Ok(DateSegmentOrder::MonthDayYear));
+        assert!("YDM".parse::<DateSegmentOrder>().is_err());
+    }
+
+    #[test]
+    fn resolve_timezone_abbreviation() {
+        let extracted = extract_absolute("2023-06-14-15-CST").unwrap();
+        assert_eq!(extracted.to_utc(), Ok(Utc.with_ymd_and_hms(2023, 6, 14, 21, 0, 0).unwrap()));
+
+        // The absolute parser keeps whatever case was typed
+        let extracted = extract_absolute("2023-06-14-15-cst").unwrap();
+        assert_eq!(extracted.to_utc(), Ok(Utc.with_ymd_and_hms(2023, 6, 14, 21, 0, 0).unwrap()));
+
+        let extracted = extract_absolute("2023-06-14T15:30:45-JST").unwrap();
+        assert_eq!(extracted.to_utc(), Ok(Utc.with_ymd_and_hms(2023, 6, 14, 6, 30, 45).unwrap()));
+    }
+
+    #[test]
+    fn resolve_without_timezone() {
+        let extracted = extract_absolute("2023.06.14").unwrap();
+        assert_eq!(extracted.to_utc(), Ok(Utc.with_ymd_and_hms(2023, 6, 14, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn resolve_cst_offset() {
+        // Central Standard Time is six hours behind UTC
+        let extracted = ExtractedTime { year: 2023, month: 1, day: 1, hour: 20, minute: 15, second: 0, timezone: Some("CST".to_string()) };
+        assert_eq!(extracted.to_utc(), Ok(Utc.with_ymd_and_hms(2023, 1, 2, 2, 15, 0).unwrap()));
+    }
+
+    #[test]
+    fn resolve_invalid_fields() {
+        let time = |month: u32, day: u32, hour: u32| ExtractedTime { year: 2023, month, day, hour, minute: 0, second: 0, timezone: None };
+        let invalid = |message: &str| Err(TimeBannerError::ParseError(message.to_string()));
+
+        assert_eq!(time(0, 1, 0).to_utc(), invalid("Invalid month: 0 (must be 1 to 12)"));
+        assert_eq!(time(13, 1, 0).to_utc(), invalid("Invalid month: 13 (must be 1 to 12)"));
+        assert_eq!(time(1, 32, 0).to_utc(), invalid("Invalid day: 32 (must be 1 to 31)"));
+        assert_eq!(time(1, 0, 0).to_utc(), invalid("Invalid day: 0 (must be 1 to 31)"));
+        assert_eq!(time(6, 14, 24).to_utc(), invalid("Invalid hour: 24 (must be 0 to 23)"));
+        assert_eq!(time(2, 29, 0).to_utc(), invalid("Invalid day: 29 is past the end of 2023-02"));
+        assert_eq!(time(2, 29, 0).validate().ok(), None);
+        assert!(time(2, 28, 23).validate().is_ok());
+    }
+
+    #[test]
+    fn extract_out_of_range() {
+        // Shaped like an absolute time, so it is extracted, but rejected when resolved
+        let extracted = extract_absolute("2023-13-40-99").unwrap();
+        assert_eq!(extracted.to_utc(), Err(TimeBannerError::ParseError("Invalid month: 13 (must be 1 to 12)".to_string())));
+        let extracted = extract_absolute("2023-06-14T12:60").unwrap();
+        assert_eq!(extracted.to_utc(), Err(TimeBannerError::ParseError("Invalid minute: 60 (must be 0 to 59)".to_string())));
+    }
+
+    #[test]
+    fn resolve_unknown_timezone() {
+        let extracted = extract_absolute("2023-06-14-15-QQQ").unwrap();
+        assert!(extracted.to_utc().is_err());
+    }
+
+    #[test]
+    fn convert_between_zones() {
+        // 15:00 in Central is 21:00 UTC, which is 06:00 the next day in Japan
+        let converted = convert_time("2023-06-14-15", Some("CST"), Some("JST"), &[], DateSegmentOrder::default()).ok().unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-15T06:00:00+09:00");
+
+        let converted = convert_time("2023-06-14-15", Some("JST"), Some("CST"), &[], DateSegmentOrder::default()).ok().unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-14T00:00:00-06:00");
+    }
+
+    #[test]
+    fn convert_defaults_to_utc() {
+        let converted = convert_time("2023-06-14-15", None, Some("JST"), &[], DateSegmentOrder::default()).ok().unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-15T00:00:00+09:00");
+
+        let converted = convert_time("2023-06-14-15", Some("JST"), None, &[], DateSegmentOrder::default()).ok().unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-14T06:00:00+00:00");
+
+        let converted = convert_time("1752170474", None, None, &[], DateSegmentOrder::default()).ok().unwrap();
+        assert_eq!(converted.to_rfc3339(), "2025-07-10T18:01:14+00:00");
+    }
+
+    #[test]
+    fn convert_from_offset_and_zone() {
+        let converted = convert_time("2023-06-14-15", Some("+05:30"), None, &[], DateSegmentOrder::default()).unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-14T09:30:00+00:00");
+        let converted = convert_time("2023-06-14-15", Some("-0600"), Some("+09:00"), &[], DateSegmentOrder::default()).unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-15T06:00:00+09:00");
+
+        // IANA zones use the offset in effect at the wall-clock time: BST in summer, GMT in winter
+        let converted = convert_time("2023-06-14-15", Some("Europe/London"), Some("Asia/Tokyo"), &[], DateSegmentOrder::default()).unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-14T23:00:00+09:00");
+        let converted = convert_time("2023-12-14-15", Some("Europe/London"), None, &[], DateSegmentOrder::default()).unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-12-14T15:00:00+00:00");
+    }
+
+    #[test]
+    fn convert_across_dst_changes() {
+        let convert = |raw: &str, from: &str| convert_time(raw, Some(from), None, &[], DateSegmentOrder::default());
+
+        // The offset is the one in effect at the wall-clock time, on either side of the change
+        assert_eq!(convert("2023-03-12-05", "America/New_York").unwrap().to_rfc3339(), "2023-03-12T09:00:00+00:00");
+        assert_eq!(convert("2023-03-12-01", "America/New_York").unwrap().to_rfc3339(), "2023-03-12T06:00:00+00:00");
+        assert_eq!(convert("2023-03-26-01:30", "Europe/Berlin").unwrap().to_rfc3339(), "2023-03-26T00:30:00+00:00");
+        assert_eq!(convert("2023-03-26-03:30", "Europe/Berlin").unwrap().to_rfc3339(), "2023-03-26T01:30:00+00:00");
+
+        // Skipped and repeated wall-clock times have no single instant
+        assert!(convert("2023-03-12-02:30", "America/New_York").is_err());
+        assert!(convert("2023-10-29-02:30", "Europe/Berlin").is_err());
+    }
+
+    #[test]
+    fn convert_date_order() {
+        let converted = convert_time("06-14-2023-15", Some("JST"), None, &[], DateSegmentOrder::MonthDayYear).unwrap();
+        assert_eq!(converted.to_rfc3339(), "2023-06-14T06:00:00+00:00");
+        assert!(convert_time("06-14-2023-15", Some("JST"), None, &[], DateSegmentOrder::DayMonthYear).is_err());
+    }
+
+    #[test]
+    fn convert_invalid_zones() {
+        assert!(convert_time("2023-06-14-15", Some("QQQ"), Some("JST"), &[], DateSegmentOrder::default()).is_err());
+        assert!(convert_time("2023-06-14-15", Some("Mars/Olympus_Mons"), None, &[], DateSegmentOrder::default()).is_err());
+        assert!(convert_time("2023-06-14-15", Some("CST"), Some("QQQ"), &[], DateSegmentOrder::default()).is_err());
+        assert!(convert_time("2023-06-14-15-CST", Some("CST"), Some("JST"), &[], DateSegmentOrder::default()).is_err());
+    }
+
+    #[test]
+    fn timezone_allowlist() {
+        let allowed = vec!["CST".to_string(), " jst".to_string()];
+        assert_eq!(parse_allowed_timezone("CST", &allowed, Utc::now()), Ok(FixedOffset::west_opt(6 * 3600).unwrap()));
+        assert_eq!(parse_allowed_timezone("JST", &allowed, Utc::now()), Ok(FixedOffset::east_opt(9 * 3600).unwrap()));
+        assert!(parse_allowed_timezone("EST", &allowed, Utc::now()).is_err());
+
+        // Unrestricted by default
+        assert!(parse_allowed_timezone("EST", &[], Utc::now()).is_ok());
+    }
+}
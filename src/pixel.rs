@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use lazy_static::lazy_static;
+use resvg::tiny_skia::Pixmap;
+
+use crate::encode::{EncodeOptions, Encoder, OutputFormat, PngEncoder};
+use crate::state::AppState;
+
+lazy_static! {
+    /// A fully transparent 1x1 PNG.
+    static ref PIXEL: Bytes = PngEncoder
+        .encode(&Pixmap::new(1, 1).unwrap(), &EncodeOptions::default())
+        .expect("Failed to encode tracking pixel");
+}
+
+/// Whether the query string asks for the tracking pixel (`pixel=1` or `pixel=true`).
+fn wants_pixel(query: Option<&str>) -> bool {
+    query.unwrap_or("").split('&').any(|pair| matches!(pair, "pixel=1" | "pixel=true"))
+}
+
+pub fn pixel_response() -> Response {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, OutputFormat::Png.mime_type()), (header::CACHE_CONTROL, "no-store")],
+        PIXEL.clone(),
+    ).into_response()
+}
+
+/// Middleware answering `?pixel=1` requests with a 1x1 transparent PNG, logging the hit instead of rendering.
+/// Only active when the operator enables `TRACKING_PIXEL`.
+pub async fn pixel_middleware<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !state.config.tracking_pixel || !wants_pixel(request.uri().query()) {
+        return next.run(request).await;
+    }
+
+    let ip = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip().to_string());
+    let referrer = request.headers().get(header::REFERER).and_then(|value| value.to_str().ok());
+    tracing::info!(
+        ip = ip.as_deref().unwrap_or("unknown"),
+        referrer = referrer.unwrap_or("none"),
+        time = %Utc::now().to_rfc3339(),
+        path = request.uri().path(),
+        "Tracking pixel hit"
+    );
+
+    pixel_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{middleware, Router, routing::get};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+    use crate::config::Configuration;
+    use crate::pixel::{pixel_middleware, pixel_response, wants_pixel};
+    use crate::state::AppState;
+
+    fn app(vars: Vec<(&str, &str)>) -> Router {
+        let config = Configuration::from_vars(vars).unwrap();
+        let state = AppState::new(config);
+        Router::new()
+            .route("/relative/:path", get(|| async { "banner" }))
+            .layer(middleware::from_fn_with_state(state.clone(), pixel_middleware))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn pixel_is_1x1_png() {
+        let body = hyper::body::to_bytes(pixel_response().into_body()).await.unwrap();
+        let reader = png::Decoder::new(&body[..]).read_info().unwrap();
+
+        assert_eq!((reader.info().width, reader.info().height), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn pixel_opt_in() {
+        let request = || Request::get("/relative/0?pixel=1").header("Referer", "https://example.com").body(Body::empty()).unwrap();
+
+        let response = app(vec![("TRACKING_PIXEL", "true")]).oneshot(request()).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "image/x-png");
+
+        // Without the operator enabling it, the parameter is ignored
+        let response = app(vec![]).oneshot(request()).await.unwrap();
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "banner");
+    }
+
+    #[test]
+    fn pixel_query() {
+        assert!(wants_pixel(Some("pixel=1")));
+        assert!(wants_pixel(Some("tz=CST&pixel=true")));
+        assert!(!wants_pixel(Some("pixel=0")));
+        assert!(!wants_pixel(Some("nopixel=1")));
+        assert!(!wants_pixel(None));
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::routes::{render_time_response, RenderQuery};
+use crate::state::AppState;
+use crate::template::OutputForm;
+
+/// The current time, truncated to the start of the minute.
+fn current_minute() -> i64 {
+    let now = Utc::now().timestamp();
+    now - now.rem_euclid(60)
+}
+
+/// Render the current-minute relative banner in every configured format, leaving the results in the render cache.
+pub fn prerender_current_minute(state: &AppState) {
+    let epoch = current_minute().to_string();
+    for extension in &state.config.prerender_formats {
+        let response = render_time_response(state, &epoch, OutputForm::Relative, extension.trim(), &RenderQuery::default());
+        if !response.status().is_success() {
+            tracing::warn!("Failed to pre-render {}.{} ({})", epoch, extension, response.status());
+        }
+    }
+}
+
+/// Periodically pre-render the current-minute banner, so the most common request never waits on rendering.
+pub async fn prerender_task(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(state.config.prerender_interval));
+    loop {
+        interval.tick().await;
+        let state = state.clone();
+        // Rendering is CPU-bound, so keep it off the async workers
+        if let Err(e) = tokio::task::spawn_blocking(move || prerender_current_minute(&state)).await {
+            tracing::error!("Pre-render task failed: {}", e);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "raster"))]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use crate::config::Configuration;
+    use crate::prerender::{current_minute, prerender_current_minute};
+    use crate::routes::{prepare_banner, RenderQuery};
+    use crate::state::AppState;
+    use crate::template::OutputForm;
+
+    #[test]
+    fn populates_cache() {
+        let config = Configuration::from_vars(vec![("PRERENDER_FORMATS", "svg,png")]).unwrap();
+        let state = AppState::new(config);
+
+        prerender_current_minute(&state);
+
+        // Either side of a minute boundary may have been rendered, depending on timing
+        let minutes = [current_minute(), current_minute() - 60];
+        for extension in ["svg", "png"] {
+            let cached = minutes.iter().find_map(|minute| {
+                let now = Utc.timestamp_opt(*minute, 0).unwrap();
+                let query = RenderQuery::default();
+                let banner = prepare_banner(&state, &minute.to_string(), OutputForm::Relative, extension, &query).unwrap();
+                state.cache.get(&banner.cache_key(now).unwrap())
+            });
+            assert!(cached.is_some(), "{} was not pre-rendered", extension);
+        }
+    }
+}
@@ -1,54 +1,339 @@
+#[cfg(feature = "raster")]
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature = "raster")]
+use lazy_static::lazy_static;
+#[cfg(feature = "raster")]
 use resvg::{tiny_skia, usvg};
+#[cfg(feature = "raster")]
 use resvg::usvg::{fontdb, TreeParsing, TreeTextToPath};
 
+#[cfg(feature = "raster")]
+use crate::color::Rgb;
+#[cfg(feature = "raster")]
+use crate::error::TimeBannerError;
+
+#[cfg(feature = "raster")]
 #[derive(Debug, Clone)]
 pub struct RenderError {
     pub message: Option<String>,
 }
 
+#[cfg(feature = "raster")]
 impl std::fmt::Display for RenderError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if self.message.is_none() {
-            write!(f, "RenderError")
-        } else {
-            write!(f, "RenderError: {}", self.message.as_ref().unwrap())
+        match &self.message {
+            Some(message) => write!(f, "RenderError: {}", message),
+            None => write!(f, "RenderError"),
         }
     }
 }
 
+/// The resolution SVG pixel units are defined against, and the default for rasterizing.
+pub const DEFAULT_DPI: f32 = 96.0;
+
+/// Accepted range for a requested DPI.
+pub const MIN_DPI: f32 = 72.0;
+pub const MAX_DPI: f32 = 1200.0;
+
+/// Parse a requested DPI, rejecting values outside of the accepted range.
+pub fn parse_dpi(raw: &str) -> Result<f32, String> {
+    let dpi = raw.parse::<f32>().map_err(|_| format!("Invalid DPI: {}", raw))?;
+    if !(MIN_DPI..=MAX_DPI).contains(&dpi) {
+        return Err(format!("DPI must be between {} and {}", MIN_DPI, MAX_DPI));
+    }
+
+    Ok(dpi)
+}
+
+/// Accepted range for a requested output scale; values outside it are clamped.
+pub const MIN_SCALE: f32 = 0.5;
+pub const MAX_SCALE: f32 = 4.0;
+
+/// Parse a requested output scale (like 2 for high-DPI displays), clamped to `MIN_SCALE` to `MAX_SCALE`.
+pub fn parse_scale(raw: &str) -> Result<f32, String> {
+    match raw.parse::<f32>() {
+        Ok(scale) if scale.is_finite() => Ok(scale.clamp(MIN_SCALE, MAX_SCALE)),
+        _ => Err(format!("Invalid scale: {}", raw)),
+    }
+}
+
+/// The most pixels a rasterized image may have, the size of the largest banner at its natural size (64 MiB of RGBA).
+/// DPI, scale and dimensions are each limited alone, but they multiply together.
+#[cfg(feature = "raster")]
+pub const MAX_OUTPUT_PIXELS: u64 = 4096 * 4096;
+
+#[cfg(feature = "raster")]
 pub struct Rasterizer {
     font_db: fontdb::Database,
 }
 
+/// Where bundled fonts are loaded from when `FONT_DIR` isn't set.
+/// Development builds run from the repository root, release builds next to the copied fonts.
+#[cfg(feature = "raster")]
+pub const DEFAULT_FONT_DIR: &str = if cfg!(debug_assertions) { "./src/fonts" } else { "./fonts" };
+
+/// The configured font directory, set once at startup.
+#[cfg(feature = "raster")]
+static FONT_DIR: OnceLock<String> = OnceLock::new();
+
+/// Load bundled fonts from `dir` in place of `DEFAULT_FONT_DIR`. Only takes effect before the first rasterization.
+#[cfg(feature = "raster")]
+pub fn set_font_dir(dir: &str) {
+    if FONT_DIR.set(dir.to_string()).is_err() {
+        tracing::warn!("Fonts were already loaded; ignoring FONT_DIR={}", dir);
+    }
+}
+
+#[cfg(feature = "raster")]
+lazy_static! {
+    // Loading fonts enumerates every system font, so it's done once and shared by every request
+    static ref SHARED_RASTERIZER: Arc<Rasterizer> = Arc::new(Rasterizer::new());
+}
+
+#[cfg(feature = "raster")]
 impl Rasterizer {
+    /// A rasterizer with the system fonts and those in the configured font directory.
     pub fn new() -> Self {
+        Self::with_font_dir(FONT_DIR.get().map_or(DEFAULT_FONT_DIR, String::as_str))
+    }
+
+    /// A rasterizer with the system fonts and those in `dir`.
+    pub fn with_font_dir(dir: &str) -> Self {
         let mut fontdb = fontdb::Database::new();
         fontdb.load_system_fonts();
-        fontdb.load_fonts_dir("./fonts");
+        fontdb.load_fonts_dir(dir);
 
         Self {
             font_db: fontdb
         }
     }
 
-    pub fn render(&self, svg_data: Vec<u8>) -> Result<Vec<u8>, RenderError> {
+    /// The process-wide rasterizer, whose fonts are loaded on first use.
+    pub fn shared() -> Arc<Rasterizer> {
+        SHARED_RASTERIZER.clone()
+    }
+
+    /// The number of font faces available for rendering text.
+    pub fn font_count(&self) -> usize {
+        self.font_db.len()
+    }
+
+    /// Whether a font family (case-insensitive) is available for rendering text.
+    pub fn has_font_family(&self, family: &str) -> bool {
+        self.font_db.faces().any(|face| face.families.iter().any(|(name, _)| name.eq_ignore_ascii_case(family)))
+    }
+
+    /// Rasterize an SVG; physical units (like inches) are converted to pixels at the given DPI,
+    /// and the resulting pixel dimensions are multiplied by `scale`. With a `fill`, the image is painted that color
+    /// before drawing, instead of starting out transparent. Images over `MAX_OUTPUT_PIXELS` are refused before any
+    /// memory is set aside for them.
+    pub fn render(&self, svg_data: Vec<u8>, dpi: f32, scale: f32, fill: Option<Rgb>) -> Result<tiny_skia::Pixmap, TimeBannerError> {
         let tree = {
-            let opt = usvg::Options::default();
-            let mut tree_result = usvg::Tree::from_data(&*svg_data, &opt);
-            if tree_result.is_err() { return Err(RenderError { message: Some("Failed to parse".to_string()) }); }
+            let opt = usvg::Options { dpi, ..usvg::Options::default() };
+            let mut tree_result = usvg::Tree::from_data(&svg_data, &opt);
+            if tree_result.is_err() { return Err(TimeBannerError::RasterizeError("Failed to parse".to_string())); }
 
             let tree = tree_result.as_mut().unwrap();
             tree.convert_text(&self.font_db);
 
-            resvg::Tree::from_usvg(&tree)
+            resvg::Tree::from_usvg(tree)
         };
 
         let pixmap_size = tree.size.to_int_size();
-        let mut pixmap = tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height()).unwrap();
-        tree.render(tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        let scaled = |pixels: u32| ((pixels as f32 * scale).ceil() as u32).max(1);
+        let (width, height) = (scaled(pixmap_size.width()), scaled(pixmap_size.height()));
+        if width as u64 * height as u64 > MAX_OUTPUT_PIXELS {
+            return Err(TimeBannerError::ParseError(format!("Image would be {}x{} pixels, over the limit of {}; lower the DPI, scale or size", width, height, MAX_OUTPUT_PIXELS)));
+        }
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)
+            .ok_or(TimeBannerError::RasterizeError("Image is too large".to_string()))?;
+        if let Some(fill) = fill {
+            pixmap.fill(tiny_skia::Color::from_rgba8(fill.r, fill.g, fill.b, 255));
+        }
+        tree.render(tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+        Ok(pixmap)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::raster::{MAX_SCALE, MIN_SCALE, parse_dpi, parse_scale};
+    #[cfg(feature = "raster")]
+    use std::sync::Arc;
+    #[cfg(feature = "raster")]
+    use chrono::{FixedOffset, TimeZone, Utc};
+    #[cfg(feature = "raster")]
+    use crate::raster::{DEFAULT_DPI, Rasterizer};
+    #[cfg(feature = "raster")]
+    use crate::color::{Rgb, WHITE};
+    #[cfg(feature = "raster")]
+    use crate::error::TimeBannerError;
+    #[cfg(feature = "raster")]
+    use crate::template::{OutputForm, render_template, RenderContext};
+
+    #[cfg(feature = "raster")]
+    fn rendered(dpi: Option<f32>) -> String {
+        rendered_with(dpi, 0.0)
+    }
 
-        pixmap
-            .encode_png()
-            .map_err(|_| RenderError { message: Some("Failed to encode".to_string()) })
+    #[cfg(feature = "raster")]
+    fn rendered_with(dpi: Option<f32>, rotate: f32) -> String {
+        render_template(context(dpi, rotate)).unwrap()
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "raster")]
+    fn context(dpi: Option<f32>, rotate: f32) -> RenderContext<'static> {
+        RenderContext {
+            output_form: OutputForm::Absolute,
+            value: Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap(),
+            tz_offset: FixedOffset::east_opt(0).unwrap(),
+            tz_name: "UTC",
+            view: "basic",
+            approximate: false,
+            exact_seconds: false,
+            prefix: None,
+            suffix: None,
+            dpi,
+            background: None,
+            foreground: None,
+            rotate,
+            as_of: false,
+            format: None,
+            now: None,
+            locale: Default::default(),
+            precision: 1,
+            theme: Default::default(),
+            font: crate::template::DEFAULT_FONT,
+            width: None,
+            height: None,
+            linecap: Default::default(),
+            clock_decimals: crate::template::DEFAULT_CLOCK_DECIMALS,
+        }
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn shared_rasterizer_loads_fonts_once() {
+        // Every caller gets the same rasterizer, and so the same font database
+        assert!(Arc::ptr_eq(&Rasterizer::shared(), &Rasterizer::shared()));
+
+        let rasterizer = Rasterizer::shared();
+        let first = rasterizer.render(rendered(None).into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        let second = Rasterizer::shared().render(rendered(None).into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        assert_eq!(first.data(), second.data());
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn bundled_fonts_available() {
+        let rasterizer = Rasterizer::shared();
+        assert!(rasterizer.has_font_family("Roboto Mono"));
+        assert!(rasterizer.has_font_family("roboto mono"));
+        assert!(!rasterizer.has_font_family("No Such Font"));
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn higher_dpi_larger_pixmap() {
+        let rasterizer = Rasterizer::new();
+
+        let screen = rasterizer.render(rendered(None).into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        assert_eq!((screen.width(), screen.height()), (512, 34));
+
+        // At the reference DPI, physical units come out at the same size as pixels
+        let reference = rasterizer.render(rendered(Some(DEFAULT_DPI)).into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        assert_eq!(reference.width(), 512);
+
+        let print = rasterizer.render(rendered(Some(300.0)).into_bytes(), 300.0, 1.0, None).unwrap();
+        assert_eq!(print.width(), 1600);
+        assert!(print.height() > screen.height());
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn rotation_swaps_dimensions() {
+        let pixmap = Rasterizer::new().render(rendered_with(None, 90.0).into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (34, 512));
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn fitted_banner_size() {
+        // The rasterizer follows the SVG's own size, so banners fitted to long text aren't cropped
+        let long = render_template(RenderContext { suffix: Some(" America/Argentina/Buenos_Aires"), ..context(None, 0.0) }).unwrap();
+        let pixmap = Rasterizer::shared().render(long.into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (924, 34));
+
+        let fixed = render_template(RenderContext { width: Some(300), height: Some(60), ..context(None, 0.0) }).unwrap();
+        let pixmap = Rasterizer::shared().render(fixed.into_bytes(), DEFAULT_DPI, 1.0, None).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (300, 60));
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn renders_to_the_edges() {
+        // The background covers the whole banner, so every corner is drawn when nothing is cropped or inset
+        let svg = render_template(RenderContext { background: Some(Rgb { r: 0x22, g: 0x22, b: 0x22 }), ..context(None, 0.0) }).unwrap();
+        for scale in [1.0, 2.0] {
+            let pixmap = Rasterizer::shared().render(svg.clone().into_bytes(), DEFAULT_DPI, scale, None).unwrap();
+            let (right, bottom) = (pixmap.width() - 1, pixmap.height() - 1);
+            for (x, y) in [(0, 0), (right, 0), (0, bottom), (right, bottom)] {
+                assert_eq!(pixmap.pixel(x, y).unwrap().alpha(), 255, "corner ({}, {}) at scale {}", x, y, scale);
+            }
+        }
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn opaque_fill() {
+        let corner = |fill| Rasterizer::shared().render(rendered(None).into_bytes(), DEFAULT_DPI, 1.0, fill).unwrap().pixel(0, 0).unwrap();
+
+        assert_eq!(corner(None).alpha(), 0);
+        let filled = corner(Some(WHITE));
+        assert_eq!((filled.red(), filled.green(), filled.blue(), filled.alpha()), (255, 255, 255, 255));
+        let filled = corner(Some(Rgb { r: 0x22, g: 0x22, b: 0x22 }));
+        assert_eq!((filled.red(), filled.alpha()), (0x22, 255));
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn scaled_pixmap() {
+        let rasterizer = Rasterizer::shared();
+        let doubled = rasterizer.render(rendered(None).into_bytes(), DEFAULT_DPI, 2.0, None).unwrap();
+        assert_eq!((doubled.width(), doubled.height()), (1024, 68));
+
+        let halved = rasterizer.render(rendered(None).into_bytes(), DEFAULT_DPI, 0.5, None).unwrap();
+        assert_eq!((halved.width(), halved.height()), (256, 17));
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn pixel_limit() {
+        let rasterizer = Rasterizer::shared();
+        assert!(rasterizer.render(rendered(Some(1200.0)).into_bytes(), 1200.0, 1.0, None).is_ok());
+
+        // Allowed on their own, but together they'd be 25600x1700 pixels
+        let oversized = rasterizer.render(rendered(Some(1200.0)).into_bytes(), 1200.0, 4.0, None);
+        assert!(matches!(oversized, Err(TimeBannerError::ParseError(_))), "{:?}", oversized.map(|pixmap| pixmap.width()));
+    }
+
+    #[test]
+    fn scale_range() {
+        assert_eq!(parse_scale("2"), Ok(2.0));
+        assert_eq!(parse_scale("1.5"), Ok(1.5));
+        assert_eq!(parse_scale("10"), Ok(MAX_SCALE));
+        assert_eq!(parse_scale("0"), Ok(MIN_SCALE));
+        assert!(parse_scale("NaN").is_err());
+        assert!(parse_scale("big").is_err());
+    }
+
+    #[test]
+    fn dpi_range() {
+        assert_eq!(parse_dpi("300"), Ok(300.0));
+        assert!(parse_dpi("10").is_err());
+        assert!(parse_dpi("5000").is_err());
+        assert!(parse_dpi("high").is_err());
+    }
+}
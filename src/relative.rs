@@ -1,6 +1,13 @@
-use chrono::Duration;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDateTime, TimeZone, Utc};
 use lazy_static::lazy_static;
-use regex::Regex;
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::error::TimeBannerError;
+use crate::parse::{DateSegmentOrder, extract_absolute_with};
 
 pub trait Months {
     fn months(count: i32) -> Self;
@@ -12,86 +19,397 @@ impl Months for Duration {
     }
 }
 
+/// How calendar month addition handles a day that doesn't exist in the target month (like February 31st).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Land on the last day of the target month: Jan 31 + 1 month is Feb 28 (or 29).
+    #[default]
+    Clamp,
+    /// Carry the extra days into the next month: Jan 31 + 1 month is Mar 3 (or Mar 2 in leap years).
+    Skip,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" => Ok(OverflowPolicy::Clamp),
+            "skip" => Ok(OverflowPolicy::Skip),
+            _ => Err(format!("Unknown overflow policy: {}", s)),
+        }
+    }
+}
+
+/// Add calendar months to a time, resolving days past the end of the target month with the given policy.
+/// Returns None if the result is out of range.
+pub fn add_months(anchor: DateTime<Utc>, months: i32, overflow: OverflowPolicy) -> Option<DateTime<Utc>> {
+    let calendar_months = chrono::Months::new(months.unsigned_abs());
+    // chrono clamps to the end of the month on its own
+    let clamped = if months >= 0 {
+        anchor.checked_add_months(calendar_months)
+    } else {
+        anchor.checked_sub_months(calendar_months)
+    }?;
+
+    match overflow {
+        OverflowPolicy::Clamp => Some(clamped),
+        OverflowPolicy::Skip => clamped.checked_add_signed(Duration::days((anchor.day() - clamped.day()) as i64)),
+    }
+}
 
 lazy_static! {
     static ref FULL_RELATIVE_PATTERN : Regex = Regex::new(concat!(
-        "(?<sign>[-+])?",
-        r"(?:(?<year>\d+)\s?(?:y|yrs?|years?))?",
-        r"(?:(?<month>\d+)\s?(?:mon|months?))?",
-        r"(?:(?<week>\d+)\s?(?:w|wks?|weeks?))?",
-        r"(?:(?<day>\d+)\s?(?:d|days?))?",
-        r"(?:(?<hour>\d+)\s?(?:h|hrs?|hours?))?",
-        r"(?:(?<minute>\d+)\s?(?:m|mins?|minutes?))?",
-        r"(?:(?<second>\d+)\s?(?:s|secs?|seconds?))?")).unwrap();
-}
-
-pub fn parse_duration(str: &str) -> Result<Duration, String> {
-    let capture = FULL_RELATIVE_PATTERN.captures(str).unwrap();
+        "^(?<sign>[-+])?",
+        r"(?:(?<year>\d+(?:\.\d+)?)\s?(?:years?|yrs?|y))?",
+        r"(?:(?<month>\d+(?:\.\d+)?)\s?(?:months?|mon))?",
+        r"(?:(?<week>\d+(?:\.\d+)?)\s?(?:weeks?|wks?|w))?",
+        r"(?:(?<day>\d+(?:\.\d+)?)\s?(?:days?|d))?",
+        r"(?:(?<hour>\d+(?:\.\d+)?)\s?(?:hours?|hrs?|h))?",
+        r"(?:(?<minute>\d+(?:\.\d+)?)\s?(?:minutes?|mins?|m))?",
+        r"(?:(?<second>\d+(?:\.\d+)?)\s?(?:seconds?|secs?|s))?",
+        r"(?:(?<millisecond>\d+(?:\.\d+)?)\s?(?:milliseconds?|msecs?|ms))?$")).unwrap();
+
+    // The same components as above, in any order and possibly repeated, to tell misordered input apart from garbage
+    static ref ANY_ORDER_PATTERN : Regex = Regex::new(concat!(
+        r"^[-+]?(?:\d+(?:\.\d+)?\s?",
+        r"(?:years?|yrs?|y|months?|mon|weeks?|wks?|w|days?|d|hours?|hrs?|h|minutes?|mins?|m|seconds?|secs?|s|milliseconds?|msecs?|ms))+$")).unwrap();
+
+    // Commas, "and" and extra spaces between a unit and the next quantity, as in "1 day, 2 hours" or "3 weeks and 4 days"
+    static ref COMPONENT_SEPARATOR : Regex = Regex::new(r"(?i)([a-z])\s*(?:,\s*)?(?:and\s+)?(\d)").unwrap();
+
+    static ref SCIENTIFIC_QUANTITY : Regex = Regex::new(r"\d[eE][-+]?\d").unwrap();
+
+    // "M" is months in the date part and minutes after the "T"
+    static ref ISO8601_DURATION_PATTERN : Regex = Regex::new(concat!(
+        "^(?<sign>[-+])?P",
+        r"(?:(?<year>\d+(?:\.\d+)?)Y)?",
+        r"(?:(?<month>\d+(?:\.\d+)?)M)?",
+        r"(?:(?<week>\d+(?:\.\d+)?)W)?",
+        r"(?:(?<day>\d+(?:\.\d+)?)D)?",
+        r"(?:T(?:(?<hour>\d+(?:\.\d+)?)H)?(?:(?<minute>\d+(?:\.\d+)?)M)?(?:(?<second>\d+(?:\.\d+)?)S)?)?$")).unwrap();
+}
+
+/// Milliseconds in one of each duration unit, in the order they appear in `FULL_RELATIVE_PATTERN`.
+/// Years are 365.25 days and months are a twelfth of that.
+fn unit_milliseconds() -> [(&'static str, i64); 8] {
+    [
+        ("year", 365 * 86_400_000 + 6 * 3_600_000),
+        ("month", Duration::months(1).num_milliseconds()),
+        ("week", 7 * 86_400_000),
+        ("day", 86_400_000),
+        ("hour", 3_600_000),
+        ("minute", 60_000),
+        ("second", 1_000),
+        ("millisecond", 1),
+    ]
+}
+
+/// Longest duration accepted in either direction, in years. Anything longer is almost certainly a mistake, and
+/// would only lead to arithmetic at the edge of what dates can represent.
+pub const MAX_DURATION_YEARS: i64 = 10_000;
+
+/// Reject durations longer than `MAX_DURATION_YEARS`.
+fn within_duration_limit(value: Duration, raw: &str) -> Result<Duration, TimeBannerError> {
+    let limit = Duration::milliseconds(unit_milliseconds()[0].1 * MAX_DURATION_YEARS);
+    if value > limit || value < -limit {
+        return Err(TimeBannerError::ParseError(format!("Duration exceeds {} years: {}", MAX_DURATION_YEARS, raw)));
+    }
+
+    Ok(value)
+}
+
+/// Maximum digits in either part of a single quantity, checked before parsing so absurd inputs fail cleanly.
+const MAX_QUANTITY_DIGITS: usize = 12;
+
+/// Split a (possibly fractional) quantity into its whole part and its fraction.
+/// Quantities with more than `MAX_QUANTITY_DIGITS` digits in either part are rejected.
+fn split_quantity(raw: &str, unit: &'static str) -> Result<(i64, f64), TimeBannerError> {
+    let invalid = || TimeBannerError::DurationError { unit, value: raw.to_string() };
+    let (whole, fraction) = raw.split_once('.').unwrap_or((raw, ""));
+    if whole.len() > MAX_QUANTITY_DIGITS || fraction.len() > MAX_QUANTITY_DIGITS {
+        return Err(invalid());
+    }
+
+    let whole = whole.parse::<i64>().map_err(|_| invalid())?;
+    let fraction = match fraction {
+        "" => 0.0,
+        fraction => format!("0.{}", fraction).parse::<f64>().map_err(|_| invalid())?,
+    };
+
+    Ok((whole, fraction))
+}
+
+/// Convert a (possibly fractional) quantity of a unit into a Duration.
+/// Whole quantities are exact; fractional parts are rounded to the nearest millisecond per component.
+fn parse_component(raw: &str, unit: &'static str, unit_ms: i64) -> Result<Duration, TimeBannerError> {
+    let (whole, fraction) = split_quantity(raw, unit)?;
+
+    whole.checked_mul(unit_ms)
+        .and_then(|whole_ms| whole_ms.checked_add((fraction * unit_ms as f64).round() as i64))
+        .map(Duration::milliseconds)
+        .ok_or_else(|| TimeBannerError::DurationError { unit, value: raw.to_string() })
+}
+
+/// Remove the separators people put between components when writing durations out, so "1 day, 2 hours" becomes "1 day2 hours".
+fn join_components(str: &str) -> Cow<'_, str> {
+    COMPONENT_SEPARATOR.replace_all(str, "$1$2")
+}
+
+/// Match a shorthand duration like "+1y2mon3w4d5h6m7s" against the whole input (ignoring surrounding whitespace).
+/// Units must go from largest to smallest, each at most once; "1d2y" is rejected rather than read as part of it.
+fn capture_duration(str: &str) -> Result<Captures<'_>, TimeBannerError> {
+    if SCIENTIFIC_QUANTITY.is_match(str) {
+        return Err(TimeBannerError::ParseError(format!("Quantities must be plain decimal numbers, not scientific notation: {}", str)));
+    }
+
+    let trimmed = str.trim();
+    FULL_RELATIVE_PATTERN.captures(trimmed).ok_or_else(|| match ANY_ORDER_PATTERN.is_match(trimmed) {
+        true => TimeBannerError::ParseError(format!("Duration units must go from largest to smallest, each at most once: {}", str)),
+        false => TimeBannerError::ParseError(format!("Could not parse duration from {}", str)),
+    })
+}
+
+/// Parse a duration like "+1y2mon3w4d5h6m7s500ms", where each quantity may be fractional ("1.5h").
+/// Every unit has a fixed length here; see `parse_duration_from` for calendar-accurate years and months.
+#[allow(dead_code)]
+pub fn parse_duration(str: &str) -> Result<Duration, TimeBannerError> {
+    let joined = join_components(str);
+    let capture = capture_duration(&joined)?;
+
     let mut value = Duration::zero();
+    for (unit, unit_ms) in unit_milliseconds() {
+        if let Some(raw) = capture.name(unit) {
+            let component = parse_component(raw.as_str(), unit, unit_ms)?;
+            value = value.checked_add(&component)
+                .ok_or(TimeBannerError::ParseError(format!("Duration is out of range: {}", str)))?;
+        }
+    }
+
+    within_duration_limit(if capture.name("sign").map(|sign| sign.as_str()) == Some("-") { -value } else { value }, str)
+}
 
-    if let Some(raw_year) = capture.name("year") {
-        value = value + match raw_year.as_str().parse::<i64>() {
-            Ok(year) => Duration::days(year * 365) + (if year > 0 { Duration::hours(6) * year as i32 } else { Duration::zero() }),
-            Err(e) => return Err(format!("Could not parse year from {} ({})", raw_year.as_str(), e.to_string()))
-        };
+/// Strip the natural-language direction words off a duration, returning whether they reverse it.
+/// A leading "next" or "in" points forward, a leading "last" or a trailing "ago" points back.
+/// A keyword already decides the direction, so it cannot be combined with an explicit sign or with another keyword.
+fn normalize_relative(raw: &str) -> Result<(bool, &str), TimeBannerError> {
+    let trimmed = raw.trim();
+    let (leading, rest) = match trimmed.split_once(char::is_whitespace) {
+        Some((keyword, rest)) => match keyword.to_ascii_lowercase().as_str() {
+            "next" | "in" => (Some(false), rest.trim_start()),
+            "last" => (Some(true), rest.trim_start()),
+            _ => (None, trimmed),
+        },
+        None => (None, trimmed),
+    };
+    let (ago, rest) = match rest.rsplit_once(char::is_whitespace) {
+        Some((rest, keyword)) if keyword.eq_ignore_ascii_case("ago") => (true, rest.trim_end()),
+        _ => (false, rest),
+    };
+
+    if leading.is_some() && ago {
+        return Err(TimeBannerError::ParseError(format!("Cannot combine a leading direction keyword with 'ago': {}", raw)));
+    }
+    if (leading.is_some() || ago) && rest.starts_with(['+', '-']) {
+        return Err(TimeBannerError::ParseError(format!("Cannot combine a sign with a direction keyword: {}", raw)));
     }
 
-    if let Some(raw_month) = capture.name("month") {
-        value = value + match raw_month.as_str().parse::<i32>() {
-            Ok(month) => Duration::months(month),
-            Err(e) => return Err(format!("Could not parse month from {} ({})", raw_month.as_str(), e.to_string()))
-        };
+    Ok((leading.unwrap_or(ago), rest))
+}
+
+/// Apply a shorthand duration to an anchor time, using calendar arithmetic for years and months:
+/// "+1mon" from January 31st lands at the end of February (per the overflow policy) rather than 30.44 days later.
+/// Smaller units keep their fixed lengths, as days are always 24 hours in UTC.
+/// Direction words set the direction, so "last 3 days" and "3 days ago" are both three days back.
+pub fn parse_duration_from(anchor: DateTime<Utc>, str: &str, overflow: OverflowPolicy) -> Result<DateTime<Utc>, TimeBannerError> {
+    let (reverse, rest) = normalize_relative(str)?;
+    let joined = join_components(rest);
+    let capture = capture_duration(&joined)?;
+    let negative = reverse ^ (capture.name("sign").map(|sign| sign.as_str()) == Some("-"));
+    offset_by_components(anchor, &capture, negative, overflow, str)
+}
+
+/// Apply the captured components of a duration to an anchor time, as calendar months plus a fixed-length remainder.
+fn offset_by_components(anchor: DateTime<Utc>, capture: &Captures, negative: bool, overflow: OverflowPolicy, str: &str) -> Result<DateTime<Utc>, TimeBannerError> {
+    let out_of_range = || TimeBannerError::ParseError(format!("Duration is out of range: {}", str));
+
+    // Fractional years and months contribute whole months where they can, and a fixed-length remainder otherwise
+    let mut months: i64 = 0;
+    let mut fractional_months = 0.0;
+    for (unit, months_per_unit) in [("year", 12), ("month", 1)] {
+        if let Some(raw) = capture.name(unit) {
+            let (whole, fraction) = split_quantity(raw.as_str(), unit)?;
+            months = whole.checked_mul(months_per_unit)
+                .and_then(|whole_months| months.checked_add(whole_months))
+                .ok_or_else(out_of_range)?;
+            fractional_months += fraction * months_per_unit as f64;
+        }
     }
+    months = months.checked_add(fractional_months.trunc() as i64).ok_or_else(out_of_range)?;
+    let mut fixed = Duration::milliseconds((fractional_months.fract() * Duration::months(1).num_milliseconds() as f64).round() as i64);
 
-    if let Some(raw_week) = capture.name("week") {
-        value = value + match raw_week.as_str().parse::<i64>() {
-            Ok(week) => Duration::days(7) * week as i32,
-            Err(e) => return Err(format!("Could not parse week from {} ({})", raw_week.as_str(), e.to_string()))
-        };
+    for (unit, unit_ms) in unit_milliseconds().into_iter().skip(2) {
+        if let Some(raw) = capture.name(unit) {
+            let component = parse_component(raw.as_str(), unit, unit_ms)?;
+            fixed = fixed.checked_add(&component).ok_or_else(out_of_range)?;
+        }
     }
 
-    if let Some(raw_day) = capture.name("day") {
-        value = value + match raw_day.as_str().parse::<i64>() {
-            Ok(day) => Duration::days(day),
-            Err(e) => return Err(format!("Could not parse day from {} ({})", raw_day.as_str(), e.to_string()))
-        };
+    if negative {
+        months = -months;
+        fixed = -fixed;
     }
 
-    if let Some(raw_hour) = capture.name("hour") {
-        value = value + match raw_hour.as_str().parse::<i64>() {
-            Ok(hour) => Duration::hours(hour),
-            Err(e) => return Err(format!("Could not parse hour from {} ({})", raw_hour.as_str(), e.to_string()))
-        };
+    // Calendar months vary in length, so the limit is checked with average ones
+    if months.abs() > MAX_DURATION_YEARS * 12 {
+        return Err(TimeBannerError::ParseError(format!("Duration exceeds {} years: {}", MAX_DURATION_YEARS, str)));
     }
+    let months = i32::try_from(months).map_err(|_| out_of_range())?;
+    within_duration_limit(Duration::months(months) + fixed, str)?;
+
+    add_months(anchor, months, overflow)
+        .and_then(|time| time.checked_add_signed(fixed))
+        .ok_or_else(out_of_range)
+}
+
+/// Capture the components of an ISO 8601 duration like "P1Y2M3DT4H5M6S" or "-PT30M".
+fn capture_iso8601_duration(raw: &str) -> Result<Captures<'_>, TimeBannerError> {
+    let invalid = || TimeBannerError::ParseError(format!("Invalid ISO 8601 duration: {}", raw));
+    let capture = ISO8601_DURATION_PATTERN.captures(raw.trim()).ok_or_else(invalid)?;
 
-    if let Some(raw_minute) = capture.name("minute") {
-        value = value + match raw_minute.as_str().parse::<i64>() {
-            Ok(minute) => Duration::minutes(minute),
-            Err(e) => return Err(format!("Could not parse minute from {} ({})", raw_minute.as_str(), e.to_string()))
-        };
+    // At least one component is required, and a "T" must be followed by one
+    let has_component = unit_milliseconds().iter().any(|(unit, _)| capture.name(unit).is_some());
+    if !has_component || raw.trim_end().ends_with('T') {
+        return Err(invalid());
     }
 
-    if let Some(raw_second) = capture.name("second") {
-        value = value + match raw_second.as_str().parse::<i64>() {
-            Ok(second) => Duration::seconds(second),
-            Err(e) => return Err(format!("Could not parse second from {} ({})", raw_second.as_str(), e.to_string()))
-        };
+    Ok(capture)
+}
+
+/// Parse an ISO 8601 duration, using the same unit lengths as `parse_duration`.
+/// See `parse_iso8601_duration_from` for calendar-accurate years and months.
+#[allow(dead_code)]
+pub fn parse_iso8601_duration(raw: &str) -> Result<Duration, TimeBannerError> {
+    let invalid = || TimeBannerError::ParseError(format!("Invalid ISO 8601 duration: {}", raw));
+    let capture = capture_iso8601_duration(raw)?;
+
+    let mut value = Duration::zero();
+    for (unit, unit_ms) in unit_milliseconds() {
+        if let Some(raw_component) = capture.name(unit) {
+            let component = parse_component(raw_component.as_str(), unit, unit_ms)?;
+            value = value.checked_add(&component).ok_or_else(invalid)?;
+        }
     }
 
-    if let Some(raw_sign) = capture.name("sign") {
-        match raw_sign.as_str() {
-            "-" => value = -value,
-            "+" => (),
-            _ => return Err(format!("Could not parse sign from {}", raw_sign.as_str()))
-        };
+    within_duration_limit(if capture.name("sign").map(|sign| sign.as_str()) == Some("-") { -value } else { value }, raw)
+}
+
+/// Apply an ISO 8601 duration to an anchor time, with calendar years and months like `parse_duration_from`.
+pub fn parse_iso8601_duration_from(anchor: DateTime<Utc>, raw: &str, overflow: OverflowPolicy) -> Result<DateTime<Utc>, TimeBannerError> {
+    let capture = capture_iso8601_duration(raw)?;
+    let negative = capture.name("sign").map(|sign| sign.as_str()) == Some("-");
+    offset_by_components(anchor, &capture, negative, overflow, raw)
+}
+
+/// Parse a raw time value into a UTC DateTime.
+/// Plain integers are treated as Unix epoch seconds, ISO 8601 timestamps with an offset are normalized to UTC, absolute times like "2023-06-14-15-CST" are resolved
+/// through their timezone, and anything else is parsed as a duration relative to now: either ISO 8601 ("PT30M")
+/// or the shorthand form (optionally written as "in 3 days", "3 days ago", "next 3 days" or "last 3 days").
+pub fn parse_time_value(raw: &str) -> Result<DateTime<Utc>, TimeBannerError> {
+    parse_time_value_with(raw, ParseOptions::default())
+}
+
+/// Per-request choices for how ambiguous time values are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseOptions {
+    /// How adding months resolves days past the end of the target month.
+    pub overflow: OverflowPolicy,
+    /// The segment order of numeric absolute dates.
+    pub order: DateSegmentOrder,
+    /// The time durations are measured from, in place of the current time.
+    pub now: Option<DateTime<Utc>>,
+}
+
+/// Parse a raw time value like `parse_time_value`, with the given options.
+pub fn parse_time_value_with(raw: &str, options: ParseOptions) -> Result<DateTime<Utc>, TimeBannerError> {
+    parse_time_value_with_strategy(raw, options).map(|(time, _)| time)
+}
+
+/// Epochs at least this large are taken to be in milliseconds. As seconds they'd be over 30,000 years away, while
+/// JavaScript's `Date.now()` has been 13 digits long since 2001.
+const MILLISECOND_EPOCH_THRESHOLD: i64 = 1_000_000_000_000;
+
+/// Convert a Unix epoch into a DateTime, reading it as milliseconds when it's too large to be seconds.
+fn epoch_to_datetime(epoch: i64) -> Option<DateTime<Utc>> {
+    if epoch.unsigned_abs() >= MILLISECOND_EPOCH_THRESHOLD as u64 {
+        return Utc.timestamp_millis_opt(epoch).single();
     }
 
-    Ok(value)
+    NaiveDateTime::from_timestamp_opt(epoch, 0).map(|naive| DateTime::<Utc>::from_utc(naive, Utc))
+}
+
+/// Offset timestamps that aren't quite RFC 3339, with no colon in the offset ("+0500") or a space before the time.
+const OFFSET_TIMESTAMP_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f%z"];
+
+/// Parse an ISO 8601 timestamp carrying its own offset, like "2025-01-17T14:30:00Z" or "2025-01-17T14:30:00+05:00".
+fn parse_offset_timestamp(raw: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(raw).ok().or_else(|| {
+        OFFSET_TIMESTAMP_FORMATS.iter().find_map(|format| DateTime::parse_from_str(raw, format).ok())
+    })
+}
+
+/// Parse a reference time for `ParseOptions::now`, given as Unix epoch seconds.
+pub fn parse_reference_time(raw: &str) -> Result<DateTime<Utc>, String> {
+    raw.parse::<i64>().ok()
+        .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())
+        .ok_or_else(|| format!("Invalid reference time: {}", raw))
+}
+
+/// Which of the strategies in `parse_time_value` recognized an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseStrategy {
+    /// Unix epoch seconds, like "1686744000".
+    Epoch,
+    /// An ISO 8601 timestamp with an offset, like "2025-01-17T14:30:00Z".
+    Rfc3339,
+    /// A date and time with a timezone abbreviation, like "2023-06-14-15-CST".
+    Absolute,
+    /// An ISO 8601 duration from now, like "PT30M".
+    Iso8601,
+    /// A shorthand duration from now, like "+1d2h" or "3 days ago".
+    Duration,
+}
+
+/// Parse a raw time value like `parse_time_value_with`, also reporting which strategy matched it.
+pub fn parse_time_value_with_strategy(raw: &str, options: ParseOptions) -> Result<(DateTime<Utc>, ParseStrategy), TimeBannerError> {
+    let now = options.now.unwrap_or_else(Utc::now);
+    if let Ok(epoch) = raw.parse::<i64>() {
+        return epoch_to_datetime(epoch)
+            .map(|time| (time, ParseStrategy::Epoch))
+            .ok_or(TimeBannerError::ParseError("Input was not a valid DateTime".to_string()));
+    }
+
+    if let Some(timestamp) = parse_offset_timestamp(raw) {
+        return Ok((timestamp.with_timezone(&Utc), ParseStrategy::Rfc3339));
+    }
+
+    if let Some(extracted) = extract_absolute_with(raw, options.order) {
+        return extracted.to_utc().map(|time| (time, ParseStrategy::Absolute));
+    }
+
+    if raw.trim_start_matches(['+', '-']).starts_with('P') {
+        return parse_iso8601_duration_from(now, raw, options.overflow).map(|time| (time, ParseStrategy::Iso8601));
+    }
+
+    parse_duration_from(now, raw, options.overflow).map(|time| (time, ParseStrategy::Duration))
 }
 
+#[cfg(test)]
 mod tests {
-    use chrono::Duration;
-    use crate::relative::{Months, parse_duration};
+    use chrono::{Duration, TimeZone, Utc};
+    use crate::error::TimeBannerError;
+    use crate::relative::{add_months, Months, OverflowPolicy, parse_duration, parse_duration_from, parse_iso8601_duration, parse_iso8601_duration_from, parse_reference_time, parse_time_value, parse_time_value_with_strategy, ParseOptions, ParseStrategy};
 
     #[test]
     fn parse_empty() {
@@ -100,6 +418,138 @@ mod tests {
         assert_eq!(parse_duration("  "), Ok(Duration::zero()));
     }
 
+    #[test]
+    fn parse_garbage() {
+        assert!(parse_duration("5q").is_err());
+        assert!(parse_duration("abc").is_err());
+        assert!(parse_duration("1y garbage").is_err());
+        assert!(parse_duration("+banana").is_err());
+        assert!(parse_duration_from(Utc::now(), "next week", OverflowPolicy::Clamp).is_err());
+    }
+
+    #[test]
+    fn parse_fractional() {
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("+0.5d"), Ok(Duration::hours(12)));
+        assert_eq!(parse_duration("2.5d"), Ok(Duration::hours(60)));
+        assert_eq!(parse_duration("-1.25m"), Ok(-Duration::seconds(75)));
+        assert_eq!(parse_duration("0.5y"), Ok(Duration::hours(365 * 12 + 3)));
+        // Components are summed after each is converted
+        assert_eq!(parse_duration("1.5h30.5m"), Ok(Duration::hours(2) + Duration::seconds(30)));
+        assert_eq!(parse_duration("1.5h30m"), parse_duration("2h"));
+    }
+
+    #[test]
+    fn parse_fractional_rounding() {
+        // Fractions are rounded to the nearest millisecond, per component
+        assert_eq!(parse_duration("1.3333333s"), Ok(Duration::milliseconds(1333)));
+        assert_eq!(parse_duration("0.0006s"), Ok(Duration::milliseconds(1)));
+        assert_eq!(parse_duration("0.0004m0.0004s"), Ok(Duration::milliseconds(24)));
+        assert!(parse_duration("1.h").is_err());
+        assert!(parse_duration(".5h").is_err());
+    }
+
+    #[test]
+    fn parse_iso8601() {
+        assert_eq!(parse_iso8601_duration("PT1H30M").ok(), Some(Duration::minutes(90)));
+        assert_eq!(parse_iso8601_duration("P3D").ok(), Some(Duration::days(3)));
+        assert_eq!(parse_iso8601_duration("-PT30M").ok(), Some(Duration::minutes(-30)));
+        assert_eq!(parse_iso8601_duration("P2W").ok(), Some(Duration::weeks(2)));
+        assert_eq!(parse_iso8601_duration("PT0.5S").ok(), Some(Duration::milliseconds(500)));
+        assert_eq!(
+            parse_iso8601_duration("P1Y2M3DT4H5M6S").ok(),
+            parse_duration("1y2mon3d4h5m6s").ok()
+        );
+    }
+
+    #[test]
+    fn parse_iso8601_month_minute() {
+        // M is months before the T and minutes after it
+        assert_eq!(parse_iso8601_duration("P1M").ok(), Some(Duration::months(1)));
+        assert_eq!(parse_iso8601_duration("PT1M").ok(), Some(Duration::minutes(1)));
+        assert_eq!(parse_iso8601_duration("P1MT1M").ok(), Some(Duration::months(1) + Duration::minutes(1)));
+    }
+
+    #[test]
+    fn parse_iso8601_invalid() {
+        assert!(parse_iso8601_duration("P1H").is_err());
+        assert!(parse_iso8601_duration("P").is_err());
+        assert!(parse_iso8601_duration("PT").is_err());
+        assert!(parse_iso8601_duration("P1DT").is_err());
+        assert!(parse_iso8601_duration("1D").is_err());
+    }
+
+    #[test]
+    fn parse_scientific_rejected() {
+        match parse_duration("+1e3s") {
+            Err(TimeBannerError::ParseError(message)) => assert!(message.contains("scientific notation"), "{}", message),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(parse_duration("2E-1h").is_err());
+    }
+
+    #[test]
+    fn parse_overlong_quantities() {
+        assert_eq!(
+            parse_duration("999999999999999999999999999999s"),
+            Err(TimeBannerError::DurationError { unit: "second", value: "999999999999999999999999999999".to_string() })
+        );
+        assert!(parse_duration("1.0000000000001h").is_err());
+
+        // Within the digit limit, but too large for a duration or a date
+        assert_eq!(
+            parse_duration("5h999999999999y"),
+            Err(TimeBannerError::ParseError("Duration units must go from largest to smallest, each at most once: 5h999999999999y".to_string()))
+        );
+        assert_eq!(
+            parse_duration("999999999999y5h"),
+            Err(TimeBannerError::DurationError { unit: "year", value: "999999999999".to_string() })
+        );
+        assert!(parse_duration("999999999999w").is_err());
+        assert!(parse_time_value("99999999999d").is_err());
+    }
+
+    #[test]
+    fn duration_limit() {
+        let exceeds = |raw: &str| TimeBannerError::ParseError(format!("Duration exceeds 10000 years: {}", raw));
+        assert_eq!(parse_duration("+10001y").unwrap_err(), exceeds("+10001y"));
+        assert_eq!(parse_duration("-600000w").unwrap_err(), exceeds("-600000w"));
+        assert_eq!(parse_iso8601_duration("P10001Y").unwrap_err(), exceeds("P10001Y"));
+        assert_eq!(parse_time_value("+999999999years").unwrap_err(), exceeds("+999999999years"));
+        assert_eq!(parse_time_value("in 10000 years and 1 day").unwrap_err(), exceeds("in 10000 years and 1 day"));
+        assert_eq!(parse_time_value("120001 months ago").unwrap_err(), exceeds("120001 months ago"));
+
+        assert!(parse_duration("+10000y").is_ok());
+        assert!(parse_time_value("-9999y").is_ok());
+    }
+
+    #[test]
+    fn parse_separated_components() {
+        assert_eq!(parse_duration("1 day, 2 hours"), Ok(Duration::days(1) + Duration::hours(2)));
+        assert_eq!(parse_duration("3 weeks and 4 days"), Ok(Duration::weeks(3) + Duration::days(4)));
+        assert_eq!(parse_duration("1 day,2 hours,  and 30 minutes"), Ok(Duration::days(1) + Duration::hours(2) + Duration::minutes(30)));
+        assert_eq!(parse_duration("1d  2h"), Ok(Duration::days(1) + Duration::hours(2)));
+        assert_eq!(parse_duration("1d2h"), Ok(Duration::days(1) + Duration::hours(2)));
+
+        // Separators only go between components
+        assert!(parse_duration("1 2d").is_err());
+        assert!(parse_duration("1d,,2h").is_err());
+        assert!(parse_duration("1d and").is_err());
+        assert!(parse_duration("1 day,").is_err());
+    }
+
+    #[test]
+    fn parse_misordered_units() {
+        // Out of order or repeated units are rejected outright, never partially parsed
+        let misordered = |raw: &str| Err(TimeBannerError::ParseError(format!("Duration units must go from largest to smallest, each at most once: {}", raw)));
+        assert_eq!(parse_duration("1d2y"), misordered("1d2y"));
+        assert_eq!(parse_duration("5s3h"), misordered("5s3h"));
+        assert_eq!(parse_duration("1h1h"), misordered("1h1h"));
+        assert_eq!(parse_duration("-30m2d"), misordered("-30m2d"));
+        assert_eq!(parse_duration("2y1d"), Ok(Duration::hours((365 * 24 + 6) * 2) + Duration::days(1)));
+        assert_eq!(parse_duration("1d2q"), Err(TimeBannerError::ParseError("Could not parse duration from 1d2q".to_string())));
+    }
+
     #[test]
     fn parse_composite() {
         assert_eq!(parse_duration("1y2mon3w4d5h6m7s"), Ok(Duration::hours(365 * 24 + 6) + Duration::months(2) + Duration::days(3 * 7 + 4) + Duration::hours(5) + Duration::minutes(6) + Duration::seconds(7)));
@@ -160,4 +610,205 @@ mod tests {
         assert_eq!(parse_duration("60sec"), Ok(Duration::minutes(1)));
         assert_eq!(parse_duration("999seconds"), Ok(Duration::seconds(999)));
     }
+
+    #[test]
+    fn parse_millisecond() {
+        assert_eq!(parse_duration("0ms"), Ok(Duration::zero()));
+        assert_eq!(parse_duration("+500ms"), Ok(Duration::milliseconds(500)));
+        assert_eq!(parse_duration("1500ms"), Ok(Duration::milliseconds(1500)));
+        assert_eq!(parse_duration("250msec"), Ok(Duration::milliseconds(250)));
+        assert_eq!(parse_duration("2 milliseconds"), Ok(Duration::milliseconds(2)));
+        assert_eq!(parse_duration("1.5ms"), Ok(Duration::milliseconds(2)));
+    }
+
+    #[test]
+    fn parse_millisecond_minute_ambiguity() {
+        // "m" is minutes and "ms" is milliseconds, alone or together
+        assert_eq!(parse_duration("5m"), Ok(Duration::minutes(5)));
+        assert_eq!(parse_duration("5ms"), Ok(Duration::milliseconds(5)));
+        assert_eq!(parse_duration("5m5ms"), Ok(Duration::minutes(5) + Duration::milliseconds(5)));
+        assert_eq!(parse_duration("1m30s250ms"), Ok(Duration::seconds(90) + Duration::milliseconds(250)));
+    }
+
+    #[test]
+    fn parse_millisecond_ordering() {
+        // Milliseconds are the smallest unit, so they come after seconds
+        assert_eq!(parse_duration("1s500ms"), Ok(Duration::milliseconds(1500)));
+        assert_eq!(parse_duration("1 second, 500 milliseconds"), Ok(Duration::milliseconds(1500)));
+        assert!(parse_duration("500ms1s").is_err());
+    }
+
+    #[test]
+    fn parse_direction_keywords() {
+        let anchor = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let from = |raw: &str| parse_duration_from(anchor, raw, OverflowPolicy::Clamp);
+
+        assert_eq!(from("next 3 days").ok(), Some(anchor + Duration::days(3)));
+        assert_eq!(from("last 3 days").ok(), Some(anchor - Duration::days(3)));
+        assert_eq!(from("Last 2h30m").ok(), Some(anchor - Duration::hours(2) - Duration::minutes(30)));
+        assert_eq!(from("3 days").ok(), Some(anchor + Duration::days(3)));
+        assert_eq!(from("-3 days").ok(), Some(anchor - Duration::days(3)));
+        assert!(from("last -3 days").is_err());
+        assert!(from("next +3 days").is_err());
+    }
+
+    #[test]
+    fn parse_natural_language() {
+        let anchor = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let from = |raw: &str| parse_duration_from(anchor, raw, OverflowPolicy::Clamp);
+
+        assert_eq!(from("3d ago").ok(), Some(anchor - Duration::days(3)));
+        assert_eq!(from("3 days AGO").ok(), Some(anchor - Duration::days(3)));
+        assert_eq!(from("in 3 days").ok(), Some(anchor + Duration::days(3)));
+        assert_eq!(from("In 2h30m").ok(), Some(anchor + Duration::hours(2) + Duration::minutes(30)));
+        assert_eq!(from("1 month ago").ok(), Utc.with_ymd_and_hms(2023, 5, 14, 12, 0, 0).single());
+        // Surrounding whitespace is tolerated
+        assert_eq!(from("3d ago  ").ok(), Some(anchor - Duration::days(3)));
+        assert_eq!(from(" in 3 days \t").ok(), Some(anchor + Duration::days(3)));
+
+        assert!(from("in 3 days ago").is_err());
+        assert!(from("last 3 days ago").is_err());
+        assert!(from("-3d ago").is_err());
+        assert!(from("in +3d").is_err());
+        assert!(from("ago").is_err());
+        assert!(from("3dago").is_err());
+    }
+
+    #[test]
+    fn parse_strategies() {
+        let strategy = |raw: &str| parse_time_value_with_strategy(raw, ParseOptions::default()).map(|(_, strategy)| strategy);
+
+        assert_eq!(strategy("1686744000"), Ok(ParseStrategy::Epoch));
+        assert_eq!(strategy("-60"), Ok(ParseStrategy::Epoch));
+        assert_eq!(strategy("2025-01-17T14:30:00Z"), Ok(ParseStrategy::Rfc3339));
+        assert_eq!(strategy("2025-01-17T14:30:00"), Ok(ParseStrategy::Absolute));
+        assert_eq!(strategy("2023-06-14-15-CST"), Ok(ParseStrategy::Absolute));
+        assert_eq!(strategy("PT30M"), Ok(ParseStrategy::Iso8601));
+        assert_eq!(strategy("-P1D"), Ok(ParseStrategy::Iso8601));
+        assert_eq!(strategy("+1d2h"), Ok(ParseStrategy::Duration));
+        assert_eq!(strategy("3 days ago"), Ok(ParseStrategy::Duration));
+        assert!(strategy("banana").is_err());
+
+        assert_eq!(
+            parse_time_value_with_strategy("1686744000", ParseOptions::default()).map(|(time, _)| time),
+            parse_time_value("1686744000")
+        );
+    }
+
+    #[test]
+    fn offset_timestamps() {
+        let expected = Utc.with_ymd_and_hms(2025, 1, 17, 14, 30, 0).unwrap();
+        assert_eq!(parse_time_value("2025-01-17T14:30:00Z"), Ok(expected));
+        assert_eq!(parse_time_value("2025-01-17T14:30:00z"), Ok(expected));
+        assert_eq!(parse_time_value("2025-01-17T20:00:00+05:30"), Ok(expected));
+        assert_eq!(parse_time_value("2025-01-17T09:30:00-05:00"), Ok(expected));
+        assert_eq!(parse_time_value("2025-01-17T09:30:00-0500"), Ok(expected));
+        assert_eq!(parse_time_value("2025-01-17 14:30:00+00:00"), Ok(expected));
+        assert_eq!(parse_time_value("2025-01-17T14:30:00.250Z"), Ok(expected + Duration::milliseconds(250)));
+
+        assert!(parse_time_value("2025-02-30T14:30:00Z").is_err());
+        assert!(parse_time_value("2025-01-17T14:30:00+25:00").is_err());
+    }
+
+    #[test]
+    fn millisecond_epochs() {
+        let seconds = parse_time_value("1752170474").unwrap();
+        // Pasted from JavaScript's Date.now()
+        assert_eq!(parse_time_value("1752170474000"), Ok(seconds));
+        assert_eq!(parse_time_value("1752170474999"), Ok(seconds + Duration::milliseconds(999)));
+        assert_eq!(parse_time_value("-1752170474000"), parse_time_value("-1752170474"));
+
+        // The largest second counts are still seconds
+        assert_eq!(parse_time_value("999999999999").unwrap().timestamp(), 999_999_999_999);
+        // Values no clock can show are still rejected
+        assert!(parse_time_value("9223372036854775807").is_err());
+        assert!(parse_time_value("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn reference_time() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let options = ParseOptions { now: Some(now), ..Default::default() };
+        let parse = |raw: &str| parse_time_value_with_strategy(raw, options).map(|(time, _)| time);
+
+        assert_eq!(parse("+1h"), Ok(now + Duration::hours(1)));
+        assert_eq!(parse("PT30M"), Ok(now + Duration::minutes(30)));
+        // Fixed times don't depend on the reference
+        assert_eq!(parse("1686744000"), Ok(now));
+
+        assert_eq!(parse_reference_time("1686744000"), Ok(now));
+        assert!(parse_reference_time("now").is_err());
+        assert!(parse_reference_time("99999999999999999").is_err());
+    }
+
+    #[test]
+    fn calendar_months() {
+        let anchor = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        let from = |raw: &str| parse_duration_from(anchor, raw, OverflowPolicy::Clamp);
+
+        assert_eq!(from("+1mon").ok(), Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).single());
+        assert_eq!(from("+6mon").ok(), Utc.with_ymd_and_hms(2024, 7, 31, 12, 0, 0).single());
+        assert_eq!(from("-2mon").ok(), Utc.with_ymd_and_hms(2023, 11, 30, 12, 0, 0).single());
+        assert_eq!(from("last 1 month").ok(), Utc.with_ymd_and_hms(2023, 12, 31, 12, 0, 0).single());
+        assert_eq!(
+            parse_duration_from(anchor, "+1mon", OverflowPolicy::Skip).ok(),
+            Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).single()
+        );
+    }
+
+    #[test]
+    fn calendar_years() {
+        // A year from a leap day is the end of the next February, not 365.25 days later
+        let leap_day = Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap();
+        let from = |raw: &str| parse_duration_from(leap_day, raw, OverflowPolicy::Clamp);
+
+        assert_eq!(from("+1y").ok(), Utc.with_ymd_and_hms(2025, 2, 28, 0, 0, 0).single());
+        assert_eq!(from("+4y").ok(), Utc.with_ymd_and_hms(2028, 2, 29, 0, 0, 0).single());
+        assert_eq!(from("1.5y").ok(), Utc.with_ymd_and_hms(2025, 8, 29, 0, 0, 0).single());
+        assert_eq!(from("1y2mon3d4h").ok(), Utc.with_ymd_and_hms(2025, 5, 2, 4, 0, 0).single());
+        assert!(from("999999999999y").is_err());
+    }
+
+    #[test]
+    fn calendar_iso8601() {
+        // ISO 8601 years and months are calendar ones too, and follow the overflow policy
+        let anchor = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        let from = |raw: &str, overflow| parse_iso8601_duration_from(anchor, raw, overflow);
+
+        assert_eq!(from("P1M", OverflowPolicy::Clamp).ok(), Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).single());
+        assert_eq!(from("P1M", OverflowPolicy::Skip).ok(), Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).single());
+        assert_eq!(from("P1Y1MT2H", OverflowPolicy::Clamp).ok(), Utc.with_ymd_and_hms(2025, 2, 28, 14, 0, 0).single());
+        assert_eq!(from("-P2M", OverflowPolicy::Clamp).ok(), Utc.with_ymd_and_hms(2023, 11, 30, 12, 0, 0).single());
+        assert_eq!(from("PT30M", OverflowPolicy::Clamp), Ok(anchor + Duration::minutes(30)));
+
+        let options = ParseOptions { now: Some(anchor), overflow: OverflowPolicy::Skip, ..Default::default() };
+        assert_eq!(
+            parse_time_value_with_strategy("P1M", options),
+            Ok((Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).unwrap(), ParseStrategy::Iso8601))
+        );
+    }
+    #[test]
+    fn month_overflow_clamp() {
+        let anchor = Utc.with_ymd_and_hms(2023, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(add_months(anchor, 1, OverflowPolicy::Clamp), Utc.with_ymd_and_hms(2023, 2, 28, 12, 0, 0).single());
+
+        let leap = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(add_months(leap, 1, OverflowPolicy::Clamp), Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).single());
+        assert_eq!(add_months(anchor, -2, OverflowPolicy::Clamp), Utc.with_ymd_and_hms(2022, 11, 30, 12, 0, 0).single());
+    }
+
+    #[test]
+    fn month_overflow_skip() {
+        let anchor = Utc.with_ymd_and_hms(2023, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(add_months(anchor, 1, OverflowPolicy::Skip), Utc.with_ymd_and_hms(2023, 3, 3, 12, 0, 0).single());
+
+        let leap = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(add_months(leap, 1, OverflowPolicy::Skip), Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).single());
+
+        // Days that exist in the target month are unaffected by the policy
+        let mid = Utc.with_ymd_and_hms(2023, 1, 15, 12, 0, 0).unwrap();
+        assert_eq!(add_months(mid, 1, OverflowPolicy::Skip), add_months(mid, 1, OverflowPolicy::Clamp));
+        assert_eq!("SKIP".parse::<OverflowPolicy>(), Ok(OverflowPolicy::Skip));
+        assert!("wrap".parse::<OverflowPolicy>().is_err());
+    }
 }
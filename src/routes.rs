@@ -1,101 +1,1912 @@
 use axum::{http::StatusCode, response::IntoResponse};
-use axum::body::{Bytes, Full};
-use axum::extract::{Path};
-use axum::http::{header};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue};
 use axum::response::{Redirect, Response};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use axum::Json;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use chrono::{DateTime, NaiveDateTime, Offset, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use crate::error::{get_error_response, TimeBannerError};
+use crate::config::Configuration;
 
 
-use crate::parse::split_on_extension;
+use crate::color::{Rgb, Theme};
+use crate::chat::{ChatPlatform, format_chat_timestamp};
+use crate::cron::CronSchedule;
+use crate::fields::{TimeFields, TimeInfo};
+use crate::animate::{DEFAULT_FRAMES, parse_frames};
+#[cfg(feature = "raster")]
+use crate::animate::render_animation;
+use crate::encode::{EncodeOptions, parse_quality};
+use crate::encode::OutputFormat;
+use crate::parse::{convert_time, DateSegmentOrder, is_timezone_allowed, parse_allowed_timezone, parse_timezone_at, split_on_extension};
+use crate::raster::{DEFAULT_DPI, parse_dpi, parse_scale};
+#[cfg(feature = "raster")]
 use crate::raster::Rasterizer;
-use crate::template::{OutputForm, render_template, RenderContext};
+use crate::relative::{OverflowPolicy, parse_reference_time, parse_time_value, parse_time_value_with_strategy, ParseOptions, ParseStrategy};
+use crate::state::AppState;
+use crate::template::{DEFAULT_CLOCK_DECIMALS, DEFAULT_FONT, parse_dimension, render_error, resolve_font, banner_text, DiffUnit, format_difference, IsoForm, LineCap, MAX_DIFF_DECIMALS, MAX_SPARKLINE_POINTS, OutputForm, render_text, parse_clock_decimals, parse_rotation, render_sparkline, render_template, RenderContext, parse_precision, template_names, validate_format, validate_label};
 
 
-fn parse_path(path: &str) -> (&str, &str) {
+/// Split the extension off a path, leaving dots that belong to the time in place. The text after the last dot is only
+/// an extension if it names a format, or has no digits and so can't be the end of a time: "+1.5h", "PT1.5H",
+/// "2023.06.14" and "12:00:00.250Z" keep their dots, while "0.tiff" is still split (and refused by `STRICT_EXTENSIONS`).
+fn split_time_extension(path: &str) -> Option<(&str, &str)> {
     split_on_extension(path)
-        .or_else(|| Some((path, "svg")))
-        .unwrap()
-}
-
-fn handle_rasterize(data: String, extension: &str) -> Result<(&str, Bytes), TimeBannerError> {
-    match extension {
-        "svg" => Ok(("image/svg+xml", Bytes::from(data))),
-        "png" => {
-            let renderer = Rasterizer::new();
-            let raw_image = renderer.render(data.into_bytes());
-            if raw_image.is_err() {
-                return Err(TimeBannerError::RasterizeError(raw_image.unwrap_err().message.unwrap_or("Unknown error".to_string())));
+        .filter(|(_, extension)| OutputFormat::from_extension(extension).is_some() || !extension.chars().any(|c| c.is_ascii_digit()))
+}
+
+fn parse_path(path: &str) -> (&str, &str) {
+    split_time_extension(path)
+        .unwrap_or((path, "svg"))
+}
+
+/// Split the time off a path and pick the output format from the extension, the `Accept` header and `?prefer=`,
+/// consulted in the configured `FORMAT_PRECEDENCE`. Also returns whether the `Accept` header took part, see `vary_on_accept`.
+fn negotiate_path<'a>(state: &AppState, path: &'a str, headers: &HeaderMap, query: &'a RenderQuery) -> (&'a str, &'a str, bool) {
+    let (raw_time, extension) = match split_time_extension(path) {
+        Some((raw_time, extension)) => (raw_time, Some(extension)),
+        None => (path, None),
+    };
+    let accept = headers.get(header::ACCEPT).and_then(|value| value.to_str().ok());
+    let prefer = query.prefer.as_deref();
+
+    let format = state.format_precedence.negotiate(extension, accept, prefer);
+    (raw_time, format, state.format_precedence.consults_accept(extension, prefer))
+}
+
+/// Mark a response as depending on the `Accept` header, so shared caches keep a copy per format.
+fn vary_on_accept(response: impl IntoResponse, accept_consulted: bool) -> Response {
+    let mut response = response.into_response();
+    if accept_consulted {
+        response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    response
+}
+
+/// Interpret a query parameter as a boolean flag ("1" or "true").
+fn is_flag_set(value: Option<&str>) -> bool {
+    matches!(value, Some("1") | Some("true"))
+}
+
+/// Resolve an extension to an output format. Unknown extensions fall back to SVG, unless `strict` is set.
+fn resolve_format(extension: &str, strict: bool) -> Result<OutputFormat, TimeBannerError> {
+    match OutputFormat::from_extension(extension) {
+        Some(format) => Ok(format),
+        None if strict => Err(TimeBannerError::ParseError(format!("Unsupported extension: {}", extension))),
+        None => Ok(OutputFormat::Svg),
+    }
+}
+
+fn handle_rasterize(data: String, extension: &str, dpi: f32, scale: f32, strict: bool, options: &EncodeOptions) -> Result<(&'static str, Bytes), TimeBannerError> {
+    let format = resolve_format(extension, strict)?;
+
+    if format.is_textual() {
+        return Err(TimeBannerError::NotAcceptable(format!("{} is only available for single banners", format.mime_type())));
+    }
+    if !format.is_raster() {
+        return Ok((format.mime_type(), Bytes::from(data)));
+    }
+
+    rasterize(data, format, dpi, scale, options)
+}
+
+#[cfg(feature = "raster")]
+fn rasterize(data: String, format: OutputFormat, dpi: f32, scale: f32, options: &EncodeOptions) -> Result<(&'static str, Bytes), TimeBannerError> {
+    let encoder = format.encoder()
+        .ok_or_else(|| TimeBannerError::NotAcceptable(format!("No encoder for {}", format.mime_type())))?;
+
+    let pixmap = Rasterizer::shared().render(data.into_bytes(), dpi, scale, options.fill())?;
+    let bytes = encoder.encode(&pixmap, options)
+        .map_err(|e| TimeBannerError::RasterizeError(e.message.unwrap_or("Unknown error".to_string())))?;
+
+    Ok((format.mime_type(), bytes))
+}
+
+/// Without the `raster` feature only SVG can be served, so image formats are refused.
+#[cfg(not(feature = "raster"))]
+fn rasterize(_data: String, format: OutputFormat, _dpi: f32, _scale: f32, _options: &EncodeOptions) -> Result<(&'static str, Bytes), TimeBannerError> {
+    Err(TimeBannerError::NotAcceptable(format!("{} output is not available in this build", format.mime_type())))
+}
+
+/// Whether a result failed on the server's side, like a rasterization error, rather than because of the request.
+fn is_server_error<T>(result: &Result<T, TimeBannerError>) -> bool {
+    matches!(result, Err(e) if e.status_and_message().0.is_server_error())
+}
+
+/// Maximum number of parts a single multipart response may contain.
+const MAX_MULTIPART_PARTS: usize = 4;
+
+/// Rasterize the same rendered template into several formats, returned as a single `multipart/mixed` body.
+fn render_multipart(data: String, formats: &str, dpi: f32, scale: f32, strict: bool, options: &EncodeOptions) -> Response {
+    let extensions: Vec<&str> = formats.split(',').map(str::trim).filter(|e| !e.is_empty()).collect();
+    if extensions.is_empty() || extensions.len() > MAX_MULTIPART_PARTS {
+        return get_error_response(TimeBannerError::ParseError(
+            format!("Between 1 and {} formats may be requested", MAX_MULTIPART_PARTS)
+        )).into_response();
+    }
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let boundary = format!("time-banner-{:x}", nanos);
+
+    let mut body = Vec::new();
+    for extension in extensions {
+        let (mime_type, bytes) = match handle_rasterize(data.clone(), extension, dpi, scale, strict, options) {
+            Ok(part) => part,
+            Err(e) => return get_error_response(e).into_response()
+        };
+
+        body.extend_from_slice(format!("--{}\r\nContent-Type: {}\r\n\r\n", boundary, mime_type).as_bytes());
+        body.extend_from_slice(&bytes);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let content_type = format!("multipart/mixed; boundary={}", boundary);
+    (StatusCode::OK, [(header::CONTENT_TYPE, content_type)], Bytes::from(body)).into_response()
+}
+
+/// Time spent in each phase of producing a banner, reported by `?debug_timing=1`.
+#[derive(Serialize, Default)]
+struct RenderTimings {
+    parse_us: u128,
+    render_us: u128,
+    rasterize_us: u128,
+}
+
+/// Query parameters shared by every route that renders a time.
+#[derive(Deserialize, Default, Debug, Clone)]
+pub struct RenderQuery {
+    tz: Option<String>,
+    format: Option<String>,
+    now: Option<String>,
+    lang: Option<String>,
+    precision: Option<String>,
+    theme: Option<String>,
+    font: Option<String>,
+    width: Option<String>,
+    height: Option<String>,
+    scale: Option<String>,
+    opaque: Option<String>,
+    debug_timing: Option<String>,
+    formats: Option<String>,
+    approx: Option<String>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    dpi: Option<String>,
+    sri: Option<String>,
+    bg: Option<String>,
+    fg: Option<String>,
+    rotate: Option<String>,
+    overflow: Option<String>,
+    as_of: Option<String>,
+    exact_seconds: Option<String>,
+    prefer: Option<String>,
+    order: Option<String>,
+    quality: Option<String>,
+    frames: Option<String>,
+    linecap: Option<String>,
+    decimals: Option<String>,
+}
+
+/// The `Digest` header value (RFC 3230) for a response body, used for integrity checks.
+fn body_digest(body: &[u8]) -> String {
+    format!("sha-256={}", STANDARD.encode(Sha256::digest(body)))
+}
+
+/// The `Cache-Control` value for a banner: short-lived for banners that go stale as time passes, long-lived otherwise.
+fn cache_control(config: &Configuration, short_lived: bool) -> String {
+    if short_lived {
+        format!("public, max-age={}", config.cache_relative_maxage)
+    } else {
+        format!("public, max-age={}, immutable", config.cache_absolute_maxage)
+    }
+}
+
+/// A strong `ETag` for a response body: identical renders share a tag, so clients can revalidate them.
+fn entity_tag(body: &[u8]) -> String {
+    let hash = Sha256::digest(body);
+    format!("\"{}\"", hash[..16].iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+}
+
+/// Build an image response with caching headers, and a `Digest` header over the final body when requested.
+fn image_response(mime_type: &'static str, bytes: Bytes, digest: bool, cache_control: &str) -> Response {
+    let headers = [(header::CONTENT_TYPE, mime_type), (header::CACHE_CONTROL, cache_control), (header::ETAG, &entity_tag(&bytes))];
+    let mut response = (StatusCode::OK, headers, bytes.clone()).into_response();
+    if digest {
+        if let Ok(value) = HeaderValue::from_str(&body_digest(&bytes)) {
+            response.headers_mut().insert("Digest", value);
+        }
+    }
+
+    response
+}
+
+/// GIFs of banners that change over time are animated, counting along with the time. Returns the number of frames
+/// to animate with, or None for a still image.
+fn animation_frames(output_form: OutputForm, extension: &str, query: &RenderQuery) -> Result<Option<usize>, String> {
+    if !output_form.changes_over_time() || query.formats.is_some() || OutputFormat::from_extension(extension) != Some(OutputFormat::Gif) {
+        return Ok(None);
+    }
+
+    query.frames.as_deref().map_or(Ok(DEFAULT_FRAMES), parse_frames).map(Some)
+}
+
+/// Respond to a failed banner request. Banners are usually embedded as images, where a JSON error only shows up as
+/// a broken image, so image requests get the error drawn into a banner of the requested format instead.
+fn error_banner_response(error: TimeBannerError, format: Option<OutputFormat>) -> Response {
+    let Some(format) = format.filter(|format| !format.is_textual()) else {
+        return get_error_response(error).into_response();
+    };
+
+    let (code, message) = error.status_and_message();
+    let banner = render_error(&message)
+        .map_err(|e| TimeBannerError::RenderError(e.to_string()))
+        .and_then(|svg| handle_rasterize(svg, format.extension(), DEFAULT_DPI, 1.0, false, &EncodeOptions::default()));
+
+    match banner {
+        Ok((mime_type, bytes)) => (code, [(header::CONTENT_TYPE, mime_type)], bytes).into_response(),
+        // Formats this build can't draw still get the error, as JSON
+        Err(_) => get_error_response(error).into_response(),
+    }
+}
+
+/// Parse a time, render it into the requested output form and rasterize it according to the extension.
+/// The time is displayed in the given timezone, defaulting to UTC.
+pub(crate) fn render_time_response(state: &AppState, raw_time: &str, output_form: OutputForm, extension: &str, query: &RenderQuery) -> Response {
+    match render_time_banner(state, raw_time, output_form, extension, query) {
+        Ok(response) => response,
+        // Multipart requests have no single format to draw an error in
+        Err(e) if query.formats.is_some() => get_error_response(e).into_response(),
+        Err(e) => error_banner_response(e, resolve_format(extension, state.config.strict_extensions).ok()),
+    }
+}
+
+/// Render a banner like `render_time_response`, on the blocking thread pool. Rendering, rasterizing and encoding are
+/// CPU-bound and never yield, so on an async worker they'd hold up other requests and `TimeoutLayer` could never fire.
+pub(crate) async fn spawn_render(state: &AppState, raw_time: &str, output_form: OutputForm, extension: &str, query: &RenderQuery) -> Response {
+    let (state, raw_time, extension, query) = (state.clone(), raw_time.to_string(), extension.to_string(), query.clone());
+    tokio::task::spawn_blocking(move || render_time_response(&state, &raw_time, output_form, &extension, &query))
+        .await
+        .unwrap_or_else(|e| get_error_response(TimeBannerError::RenderError(e.to_string())).into_response())
+}
+
+/// A banner request with its time parsed and every option validated, ready to render.
+pub(crate) struct PreparedBanner<'a> {
+    context: RenderContext<'a>,
+    /// The raw input for times relative to now, like "+1h", whose instant moves with every request; the epoch otherwise.
+    time_key: String,
+    relative_to_now: bool,
+    /// The output format, unless the extension was refused.
+    format: Option<OutputFormat>,
+    scale: f32,
+    encode_options: EncodeOptions,
+    animation_frames: Option<usize>,
+}
+
+impl PreparedBanner<'_> {
+    /// Whether the banner goes stale as time passes: its form counts along with the time, or its time is relative to now.
+    fn short_lived(&self) -> bool {
+        self.relative_to_now || self.context.output_form.changes_over_time()
+    }
+
+    /// The render cache key: every validated input that affects the rendered image, so spellings of the same request
+    /// (`?approx=1` and `?approx=true`) share an entry and options that don't change the image (like `?sri=`) don't
+    /// split it. Banners that go stale also depend on the current time, bucketed to the minute so renders are shared
+    /// within it. Returns None for banners that aren't cached.
+    pub(crate) fn cache_key(&self, now: DateTime<Utc>) -> Option<String> {
+        let format = self.format.filter(|format| !format.is_textual())?;
+        let bucket = if self.short_lived() { now.timestamp().div_euclid(60) } else { 0 };
+        let context = &self.context;
+        let color = |color: Option<Rgb>| color.map_or(String::new(), |color| color.to_string());
+
+        Some(format!(
+            "{:?}:{}:{}:{:?}:{}:{:?}:{:?}:{:?}:{}:{}:{:?}:{:?}:{}:{}:{}:{}:{:?}:{:?}:{}:{:?}:{:?}:{:?}:{}:{:?}:{}:{}:{:?}:{:?}:{}",
+            context.output_form, self.time_key, bucket, format, context.tz_offset.local_minus_utc(), context.format,
+            context.prefix, context.suffix, context.approximate, context.exact_seconds, context.locale, context.precision,
+            context.as_of, context.now.unwrap_or_default(), color(context.background), color(context.foreground),
+            context.theme, context.font, context.rotate, context.width, context.height, context.dpi, self.scale,
+            self.encode_options.quality, color(self.encode_options.background), self.encode_options.opaque, self.animation_frames,
+            context.linecap, context.clock_decimals,
+        ))
+    }
+}
+
+/// Parse a banner's time and validate its options, without rendering anything.
+pub(crate) fn prepare_banner<'a>(state: &'a AppState, raw_time: &str, output_form: OutputForm, extension: &str, query: &'a RenderQuery) -> Result<PreparedBanner<'a>, TimeBannerError> {
+    let overflow = match query.overflow.as_deref().map(str::parse::<OverflowPolicy>).transpose() {
+        Ok(overflow) => overflow.unwrap_or_default(),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+    let order = match query.order.as_deref().map(str::parse::<DateSegmentOrder>).transpose() {
+        Ok(order) => order.unwrap_or(state.date_order),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+    let now = match query.now.as_deref().map(parse_reference_time).transpose() {
+        Ok(now) => now,
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+    let (time, strategy) = parse_time_value_with_strategy(raw_time, ParseOptions { overflow, order, now })?;
+    let relative_to_now = now.is_none() && matches!(strategy, ParseStrategy::Iso8601 | ParseStrategy::Duration);
+    let time_key = if relative_to_now { raw_time.to_string() } else { time.timestamp().to_string() };
+
+    let (tz_offset, tz_name) = match query.tz.as_deref() {
+        Some(raw_tz) => match parse_allowed_timezone(raw_tz, &state.config.allowed_timezones, time) {
+            Ok(offset) => (offset, raw_tz),
+            // Zones that may be used but aren't recognized are shown in UTC
+            Err(_) if is_timezone_allowed(raw_tz, &state.config.allowed_timezones) => (time.offset().fix(), "UTC"),
+            Err(e) => return Err(TimeBannerError::ParseError(e))
+        },
+        // The default zone was validated at startup, and isn't subject to the allowlist
+        None => match state.config.default_tz.as_deref() {
+            Some(default_tz) => match parse_timezone_at(default_tz, time) {
+                Ok(offset) => (offset, default_tz),
+                Err(e) => return Err(TimeBannerError::ParseError(e))
+            },
+            None => (time.offset().fix(), "UTC"),
+        },
+    };
+
+    let prefix = query.prefix.as_deref().map(validate_label).transpose();
+    let suffix = query.suffix.as_deref().map(validate_label).transpose();
+    let (prefix, suffix) = match (prefix, suffix) {
+        (Ok(prefix), Ok(suffix)) => (prefix, suffix),
+        (Err(e), _) | (_, Err(e)) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let format = match query.format.as_deref().map(validate_format).transpose() {
+        Ok(format) => format,
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let precision = match query.precision.as_deref().map(parse_precision).transpose() {
+        Ok(precision) => precision.unwrap_or(1),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let dpi = match query.dpi.as_deref().map(parse_dpi).transpose() {
+        Ok(dpi) => dpi,
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let background = query.bg.as_deref().map(str::parse::<Rgb>).transpose();
+    let foreground = query.fg.as_deref().map(str::parse::<Rgb>).transpose();
+    let (background, foreground) = match (background, foreground) {
+        (Ok(background), Ok(foreground)) => (background, foreground),
+        (Err(e), _) | (_, Err(e)) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let width = query.width.as_deref().map(parse_dimension).transpose();
+    let height = query.height.as_deref().map(parse_dimension).transpose();
+    let (width, height) = match (width, height) {
+        (Ok(width), Ok(height)) => (width, height),
+        (Err(e), _) | (_, Err(e)) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let theme = match query.theme.as_deref().map(str::parse::<Theme>).transpose() {
+        Ok(theme) => theme.unwrap_or_default(),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let linecap = match query.linecap.as_deref().map(str::parse::<LineCap>).transpose() {
+        Ok(linecap) => linecap.unwrap_or_default(),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let clock_decimals = match query.decimals.as_deref().map(parse_clock_decimals).transpose() {
+        Ok(decimals) => decimals.unwrap_or(DEFAULT_CLOCK_DECIMALS),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let scale = match query.scale.as_deref().map(parse_scale).transpose() {
+        Ok(scale) => scale.unwrap_or(1.0),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let rotate = match query.rotate.as_deref().map(parse_rotation).transpose() {
+        Ok(rotate) => rotate.unwrap_or(0.0),
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let encode_options = match query.quality.as_deref().map(parse_quality).transpose() {
+        Ok(quality) => EncodeOptions { quality, background, opaque: is_flag_set(query.opaque.as_deref()) },
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let animation_frames = match animation_frames(output_form, extension, query) {
+        Ok(frames) => frames,
+        Err(e) => return Err(TimeBannerError::ParseError(e))
+    };
+
+    let context = RenderContext {
+        output_form,
+        value: time,
+        tz_offset,
+        tz_name,
+        view: output_form.view(),
+        approximate: is_flag_set(query.approx.as_deref()),
+        exact_seconds: is_flag_set(query.exact_seconds.as_deref()),
+        prefix,
+        suffix,
+        dpi,
+        background,
+        foreground,
+        rotate,
+        as_of: is_flag_set(query.as_of.as_deref()),
+        format,
+        now: now.map(|now| now.timestamp()),
+        // Unsupported languages fall back to English rather than failing the banner
+        locale: query.lang.as_deref().and_then(|lang| lang.parse().ok()).unwrap_or_default(),
+        precision,
+        theme,
+        font: query.font.as_deref().map_or(DEFAULT_FONT, |font| resolve_font(font, |family| state.has_font_family(family))),
+        width,
+        height,
+        linecap,
+        clock_decimals,
+    };
+
+    Ok(PreparedBanner {
+        context,
+        time_key,
+        relative_to_now,
+        format: resolve_format(extension, state.config.strict_extensions).ok(),
+        scale,
+        encode_options,
+        animation_frames,
+    })
+}
+
+fn render_time_banner(state: &AppState, raw_time: &str, output_form: OutputForm, extension: &str, query: &RenderQuery) -> Result<Response, TimeBannerError> {
+    let debug_timing = state.config.debug_endpoints && is_flag_set(query.debug_timing.as_deref());
+    let mut timings = RenderTimings::default();
+    let digest = is_flag_set(query.sri.as_deref());
+
+    let phase = Instant::now();
+    let banner = prepare_banner(state, raw_time, output_form, extension, query)?;
+    timings.parse_us = phase.elapsed().as_micros();
+
+    // Banners of a time relative to now show a different time with every request, whatever their form
+    let cache_control = cache_control(&state.config, banner.short_lived());
+    // Multipart responses aren't cached
+    let cache_key = banner.cache_key(Utc::now()).filter(|_| query.formats.is_none());
+    if !debug_timing {
+        if let Some((mime_type, bytes)) = cache_key.as_ref().and_then(|key| state.cache.get(key)) {
+            return Ok(image_response(mime_type, bytes, digest, &cache_control));
+        }
+    }
+
+    let PreparedBanner { context, format, scale, encode_options, animation_frames, .. } = banner;
+    let dpi = context.dpi;
+
+    // Textual formats need only the banner's text, not a rendered template
+    if let Some(format) = format.filter(|format| format.is_textual()) {
+        let now = context.reference_time();
+        let text = banner_text(&context, now);
+        let body = match format {
+            OutputFormat::Json => {
+                let info = TimeInfo::new(context.value, context.tz_offset, context.tz_name, text, now);
+                match serde_json::to_vec(&info) {
+                    Ok(body) => Bytes::from(body),
+                    Err(e) => return Err(TimeBannerError::RenderError(e.to_string()))
+                }
             }
+            _ => Bytes::from(text),
+        };
+        return Ok(image_response(format.mime_type(), body, digest, &cache_control));
+    }
 
-            Ok(("image/x-png", Bytes::from(raw_image.unwrap())))
+    let rasterized = match animation_frames {
+        #[cfg(feature = "raster")]
+        Some(frames) => {
+            let phase = Instant::now();
+            let animated = render_animation(&state.rasterizer, context, frames, dpi.unwrap_or(DEFAULT_DPI), scale, encode_options.fill());
+            timings.rasterize_us = phase.elapsed().as_micros();
+            state.metrics.record_rasterize(phase.elapsed(), is_server_error(&animated));
+            animated.map(|bytes| (OutputFormat::Gif.mime_type(), bytes))
         }
-        _ => Err(TimeBannerError::RasterizeError(format!("Unsupported extension: {}", extension)))
+        _ => {
+            let phase = Instant::now();
+            let rendered_template = match render_template(context) {
+                Ok(rendered) => rendered,
+                Err(e) => return Err(TimeBannerError::RenderError(
+                    format!("Template Could Not Be Rendered :: {}", e)
+                ))
+            };
+            timings.render_us = phase.elapsed().as_micros();
+
+            if let Some(formats) = query.formats.as_deref() {
+                return Ok(render_multipart(rendered_template, formats, dpi.unwrap_or(DEFAULT_DPI), scale, state.config.strict_extensions, &encode_options));
+            }
+
+            let phase = Instant::now();
+            let rasterized = handle_rasterize(rendered_template, extension, dpi.unwrap_or(DEFAULT_DPI), scale, state.config.strict_extensions, &encode_options);
+            timings.rasterize_us = phase.elapsed().as_micros();
+            state.metrics.record_rasterize(phase.elapsed(), is_server_error(&rasterized));
+            rasterized
+        }
+    };
+
+    let (mime_type, bytes) = rasterized?;
+    if debug_timing {
+        return Ok(Json(timings).into_response());
     }
+
+    if let Some(cache_key) = cache_key {
+        state.cache.insert(cache_key, (mime_type, bytes.clone()));
+    }
+    Ok(image_response(mime_type, bytes, digest, &cache_control))
 }
 
 pub async fn index_handler() -> impl IntoResponse {
     let epoch_now = Utc::now().timestamp();
-    return Redirect::temporary(&*format!("/relative/{epoch_now}")).into_response();
+    Redirect::temporary(&format!("/relative/{epoch_now}")).into_response()
 }
 
-pub async fn relative_handler(Path(path): Path<String>) -> impl IntoResponse {
-    let (raw_time, extension) = parse_path(path.as_str());
+pub async fn relative_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("relative");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Relative, extension, &query).await, accept_consulted)
 }
 
 pub async fn fallback_handler() -> impl IntoResponse {
-    return get_error_response(TimeBannerError::NotFound).into_response();
+    get_error_response(TimeBannerError::NotFound).into_response()
+}
+
+pub async fn absolute_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("absolute");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Absolute, extension, &query).await, accept_consulted)
+}
+
+#[derive(Deserialize)]
+pub struct EpochQuery {
+    group: Option<String>,
+}
+
+pub async fn epoch_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(epoch_query): Query<EpochQuery>, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("epoch");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    let grouped = is_flag_set(epoch_query.group.as_deref());
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Epoch { grouped }, extension, &query).await, accept_consulted)
+}
+
+/// A favicon-sized badge showing how long ago (or until) a time is, like "3h", for dynamic favicons.
+pub async fn badge_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("badge");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Badge, extension, &query).await, accept_consulted)
+}
+
+pub async fn week_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("week");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Week, extension, &query).await, accept_consulted)
+}
+
+#[derive(Deserialize)]
+pub struct DigitalQuery {
+    seconds: Option<String>,
+}
+
+/// A digital clock readout of the time, like "14:30:45". Seconds are shown unless `?seconds=false`.
+pub async fn digital_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(digital_query): Query<DigitalQuery>, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("digital");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    let seconds = !matches!(digital_query.seconds.as_deref(), Some("0") | Some("false"));
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Digital { seconds }, extension, &query).await, accept_consulted)
+}
+
+#[derive(Deserialize)]
+pub struct ClockQuery {
+    seconds: Option<String>,
+}
+
+/// An analog clock face showing the time, sized for favicons. Add `?seconds=true` for a seconds hand.
+pub async fn clock_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(clock_query): Query<ClockQuery>, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("clock");
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    let seconds = is_flag_set(clock_query.seconds.as_deref());
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Clock { seconds }, extension, &query).await, accept_consulted)
+}
+
+#[derive(Deserialize)]
+pub struct IsoQuery {
+    form: Option<String>,
+}
+
+/// Render the time in an ISO 8601 representation: `?form=ordinal`, `week` or `basic` (the default).
+pub async fn iso_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(iso_query): Query<IsoQuery>, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("iso");
+    let form = match iso_query.form.as_deref().map(str::parse::<IsoForm>).transpose() {
+        Ok(form) => form.unwrap_or_default(),
+        Err(e) => return get_error_response(TimeBannerError::ParseError(e)).into_response()
+    };
+
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Iso(form), extension, &query).await, accept_consulted)
+}
+
+#[derive(Deserialize)]
+pub struct CronQuery {
+    expr: Option<String>,
+    absolute: Option<String>,
+}
+
+/// Count down to the next time a cron expression (`?expr=0 9 * * 1-5`) fires, or show it as an absolute time.
+pub async fn cron_handler(State(state): State<AppState>, Query(cron_query): Query<CronQuery>, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("cron");
+    let expr = cron_query.expr.as_deref().unwrap_or("");
+    let schedule = match expr.parse::<CronSchedule>() {
+        Ok(schedule) => schedule,
+        Err(e) => return get_error_response(TimeBannerError::ParseError(
+            format!("Invalid cron expression '{}': {}", expr, e)
+        )).into_response()
+    };
+    let next = match schedule.next_after(Utc::now()) {
+        Some(next) => next,
+        None => return get_error_response(TimeBannerError::ParseError(
+            format!("Cron expression '{}' never fires", expr)
+        )).into_response()
+    };
+
+    let output_form = if is_flag_set(cron_query.absolute.as_deref()) { OutputForm::Absolute } else { OutputForm::Relative };
+    spawn_render(&state, &next.timestamp().to_string(), output_form, "svg", &query).await
+}
+
+#[derive(Deserialize)]
+pub struct ChatQuery {
+    style: Option<String>,
+    platform: Option<String>,
+}
+
+pub async fn chat_handler(Path(path): Path<String>, Query(query): Query<ChatQuery>) -> impl IntoResponse {
+    let platform = match query.platform.as_deref().unwrap_or("discord").parse::<ChatPlatform>() {
+        Ok(platform) => platform,
+        Err(e) => return get_error_response(TimeBannerError::ParseError(e)).into_response()
+    };
+
+    let markup = parse_time_value(path.as_str())
+        .and_then(|time| {
+            format_chat_timestamp(time, query.style.as_deref().unwrap_or("R"), platform)
+                .map_err(TimeBannerError::ParseError)
+        });
+
+    match markup {
+        Ok(markup) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; charset=utf-8")], markup).into_response(),
+        Err(e) => get_error_response(e).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FieldsQuery {
+    tz: Option<String>,
+}
+
+pub async fn fields_handler(State(state): State<AppState>, Path(path): Path<String>, Query(query): Query<FieldsQuery>) -> impl IntoResponse {
+    let time = match parse_time_value(path.as_str()) {
+        Ok(time) => time,
+        Err(e) => return get_error_response(e).into_response()
+    };
+
+    let offset = match query.tz.as_deref() {
+        Some(raw_tz) => match parse_allowed_timezone(raw_tz, &state.config.allowed_timezones, time) {
+            Ok(offset) => offset,
+            Err(e) => return get_error_response(TimeBannerError::ParseError(e)).into_response()
+        },
+        None => time.offset().fix(),
+    };
+
+    Json(TimeFields::new(time, offset)).into_response()
+}
+
+#[cfg(feature = "raster")]
+#[derive(Deserialize)]
+pub struct FaviconQuery {
+    style: Option<String>,
+}
+
+#[cfg(feature = "raster")]
+pub async fn favicon_handler(State(state): State<AppState>, Query(favicon_query): Query<FaviconQuery>, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("favicon");
+    let output_form = match favicon_query.style.as_deref().unwrap_or("binary") {
+        "binary" => OutputForm::BinaryClock,
+        "clock" => OutputForm::Clock { seconds: true },
+        style => return get_error_response(TimeBannerError::ParseError(format!("Unknown favicon style: {}", style))).into_response()
+    };
+
+    // The favicon always shows the current time, so it's as short-lived as a relative banner
+    let now = Utc::now().timestamp().to_string();
+    let mut response = spawn_render(&state, &now, output_form, "png", &query).await;
+    if response.status().is_success() {
+        if let Ok(value) = HeaderValue::from_str(&cache_control(&state.config, true)) {
+            response.headers_mut().insert(header::CACHE_CONTROL, value);
+        }
+    }
+    response
+}
+
+#[derive(Deserialize)]
+pub struct ConvertQuery {
+    from: Option<String>,
+    to: Option<String>,
+    order: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Conversion {
+    from: String,
+    to: String,
+    utc: String,
+    converted: String,
+}
+
+pub async fn convert_handler(State(state): State<AppState>, Path(path): Path<String>, Query(query): Query<ConvertQuery>) -> impl IntoResponse {
+    let order = match query.order.as_deref().map(str::parse::<DateSegmentOrder>).transpose() {
+        Ok(order) => order.unwrap_or(state.date_order),
+        Err(e) => return get_error_response(TimeBannerError::ParseError(e)).into_response()
+    };
+
+    match convert_time(path.as_str(), query.from.as_deref(), query.to.as_deref(), &state.config.allowed_timezones, order) {
+        Ok(converted) => Json(Conversion {
+            from: query.from.unwrap_or("UTC".to_string()),
+            to: query.to.unwrap_or("UTC".to_string()),
+            utc: converted.with_timezone(&Utc).to_rfc3339(),
+            converted: converted.to_rfc3339(),
+        }).into_response(),
+        Err(e) => get_error_response(e).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ParseQuery {
+    input: Option<String>,
+}
+
+/// How an input was understood, for debugging ambiguous inputs.
+#[derive(Serialize)]
+pub struct ParseReport {
+    strategy: ParseStrategy,
+    resolved_iso: String,
+    resolved_epoch: i64,
+}
+
+/// Report which parsing strategy recognized `?input=...` and the time it resolved to.
+pub async fn parse_handler(Query(query): Query<ParseQuery>) -> impl IntoResponse {
+    let input = query.input.as_deref().unwrap_or("");
+    match parse_time_value_with_strategy(input, ParseOptions::default()) {
+        Ok((time, strategy)) => Json(ParseReport {
+            strategy,
+            resolved_iso: time.to_rfc3339(),
+            resolved_epoch: time.timestamp(),
+        }).into_response(),
+        Err(e) => get_error_response(e).into_response()
+    }
+}
+
+/// Plot several epochs (`?t=...&t=...`) on a tiny SVG timeline.
+pub async fn sparkline_handler(Query(params): Query<Vec<(String, String)>>) -> impl IntoResponse {
+    let raw_epochs: Vec<&str> = params.iter()
+        .filter(|(key, _)| key == "t")
+        .map(|(_, value)| value.as_str())
+        .collect();
+    if raw_epochs.is_empty() || raw_epochs.len() > MAX_SPARKLINE_POINTS {
+        return get_error_response(TimeBannerError::ParseError(
+            format!("Between 1 and {} times may be plotted", MAX_SPARKLINE_POINTS)
+        )).into_response();
+    }
+
+    let epochs = match raw_epochs.iter()
+        .map(|raw| raw.parse::<i64>().ok()
+            .filter(|epoch| NaiveDateTime::from_timestamp_opt(*epoch, 0).is_some())
+            .ok_or(TimeBannerError::ParseError(format!("Invalid epoch: {}", raw))))
+        .collect::<Result<Vec<i64>, TimeBannerError>>() {
+        Ok(epochs) => epochs,
+        Err(e) => return get_error_response(e).into_response()
+    };
+
+    match render_sparkline(&epochs) {
+        Ok(rendered) => (StatusCode::OK, [(header::CONTENT_TYPE, OutputFormat::Svg.mime_type())], rendered).into_response(),
+        Err(e) => get_error_response(TimeBannerError::RenderError(
+            format!("Template Could Not Be Rendered :: {}", e)
+        )).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    unit: Option<String>,
+    decimals: Option<String>,
+}
+
+/// Render the difference between two times, either as a span or as a total in a single unit (`?unit=hours`).
+pub async fn diff_handler(State(state): State<AppState>, Path((a, b)): Path<(String, String)>, Query(query): Query<DiffQuery>) -> impl IntoResponse {
+    state.metrics.record_request("diff");
+    let (raw_b, extension) = parse_path(b.as_str());
+    let times = parse_time_value(a.as_str()).and_then(|a| Ok((a, parse_time_value(raw_b)?)));
+    let (a, b) = match times {
+        Ok(times) => times,
+        Err(e) => return get_error_response(e).into_response()
+    };
+
+    let unit = match query.unit.as_deref().map(str::parse::<DiffUnit>).transpose() {
+        Ok(unit) => unit,
+        Err(e) => return get_error_response(TimeBannerError::ParseError(e)).into_response()
+    };
+    let decimals = match query.decimals.as_deref().map(str::parse::<usize>) {
+        Some(Ok(decimals)) if decimals <= MAX_DIFF_DECIMALS => Some(decimals),
+        Some(_) => return get_error_response(TimeBannerError::ParseError(
+            format!("Decimals must be between 0 and {}", MAX_DIFF_DECIMALS)
+        )).into_response(),
+        None => None,
+    };
+
+    let rendered = match render_text(&format_difference(a, b, unit, decimals)) {
+        Ok(rendered) => rendered,
+        Err(e) => return get_error_response(TimeBannerError::RenderError(
+            format!("Template Could Not Be Rendered :: {}", e)
+        )).into_response()
+    };
+
+    let (extension, strict) = (extension.to_string(), state.config.strict_extensions);
+    let rasterized = tokio::task::spawn_blocking(move || handle_rasterize(rendered, &extension, DEFAULT_DPI, 1.0, strict, &EncodeOptions::default()))
+        .await
+        .unwrap_or_else(|e| Err(TimeBannerError::RenderError(e.to_string())));
+    match rasterized {
+        Ok((mime_type, bytes)) => (StatusCode::OK, [(header::CONTENT_TYPE, mime_type)], bytes).into_response(),
+        Err(e) => get_error_response(e).into_response()
+    }
+}
+
+/// Liveness report: the process is running and answering requests.
+#[derive(Serialize)]
+pub struct Liveness {
+    alive: bool,
+}
+
+pub async fn liveness_handler() -> impl IntoResponse {
+    Json(Liveness { alive: true })
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], state.metrics.render())
 }
 
-pub async fn absolute_handler(Path(path): Path<String>) -> impl IntoResponse {
-    let (raw_time, extension) = parse_path(path.as_str());
+/// Readiness report: whether the process is able to serve images, not merely running.
+#[derive(Serialize)]
+pub struct Readiness {
+    ready: bool,
+    templates: Vec<String>,
+    /// Font faces available for rasterizing; null when built without the `raster` feature.
+    fonts: Option<usize>,
+}
+
+impl Readiness {
+    fn new(templates: Vec<String>, fonts: Option<usize>) -> Self {
+        Self { ready: !templates.is_empty() && fonts != Some(0), templates, fonts }
+    }
+
+    fn into_response(self) -> Response {
+        let status = if self.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+        (status, Json(self)).into_response()
+    }
+}
+
+pub async fn readiness_handler(State(state): State<AppState>) -> impl IntoResponse {
+    Readiness::new(template_names(), state.font_count()).into_response()
+}
+
+/// A loaded template and a route rendering it, for the `/templates` development listing.
+#[derive(Serialize)]
+pub struct TemplatePreview {
+    name: String,
+    preview: Option<String>,
+}
+
+/// An example route rendering the given template, if any route uses it.
+fn preview_path(name: &str, now: i64) -> Option<String> {
+    match name {
+        "basic.svg" => Some(format!("/relative/{}", now)),
+        "binary.svg" if cfg!(feature = "raster") => Some("/favicon.png".to_string()),
+        "badge.svg" => Some(format!("/badge/{}", now - 3 * 3600)),
+        "digital.svg" => Some(format!("/digital/{}", now)),
+        "clock.svg" => Some(format!("/clock/{}?seconds=true", now)),
+        "error.svg" => Some("/relative/invalid".to_string()),
+        "sparkline.svg" => Some(format!("/sparkline?t={}&t={}&t={}", now - 86400, now - 3600, now)),
+        _ => None,
+    }
+}
+
+/// List the loaded templates with preview links. Only available with `DEBUG_ENDPOINTS` enabled.
+pub async fn templates_handler(State(state): State<AppState>) -> impl IntoResponse {
+    if !state.config.debug_endpoints {
+        return get_error_response(TimeBannerError::NotFound).into_response();
+    }
+
+    let now = Utc::now().timestamp();
+    let mut previews: Vec<TemplatePreview> = template_names().into_iter()
+        .map(|name| TemplatePreview { preview: preview_path(&name, now), name })
+        .collect();
+    previews.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(previews).into_response()
 }
 
 
 // basic handler that responds with a static string
-pub async fn implicit_handler(Path(path): Path<String>) -> impl IntoResponse {
-    // Get extension if available
-    let (raw_time, extension) = parse_path(path.as_str());
+pub async fn implicit_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap, Query(query): Query<RenderQuery>) -> impl IntoResponse {
+    state.metrics.record_request("implicit");
+    // Get the time and the negotiated extension
+    let (raw_time, extension, accept_consulted) = negotiate_path(&state, path.as_str(), &headers, &query);
 
-    // Parse epoch
-    let parsed_epoch = raw_time.parse::<i64>();
-    if parsed_epoch.is_err() {
+    // Only epochs are accepted here, durations must use the explicit routes
+    if raw_time.parse::<i64>().is_err() {
         return get_error_response(TimeBannerError::ParseError("Input could not be parsed into integer.".to_string())).into_response();
     }
 
-    // Convert epoch to DateTime
-    let naive_time = NaiveDateTime::from_timestamp_opt(parsed_epoch.unwrap(), 0);
-    if naive_time.is_none() {
-        return get_error_response(TimeBannerError::ParseError("Input was not a valid DateTime".to_string())).into_response();
+    vary_on_accept(spawn_render(&state, raw_time, OutputForm::Relative, extension, &query).await, accept_consulted)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, routing::get};
+    use axum::body::Body;
+    use axum::extract::State;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use sha2::{Digest, Sha256};
+    use axum::http::{header, HeaderMap, Request, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use tower::ServiceExt;
+    use crate::config::Configuration;
+    use axum::extract::Query;
+    use axum::extract::Path;
+    use chrono::{Duration, TimeZone, Utc};
+    use crate::routes::{negotiate_path, convert_handler, CronQuery, cron_handler, ParseQuery, parse_handler, DiffQuery, diff_handler, implicit_handler, relative_handler, liveness_handler, metrics_handler, Readiness, readiness_handler, prepare_banner, render_time_response, RenderQuery, sparkline_handler, templates_handler};
+    #[cfg(feature = "raster")]
+    use crate::routes::{favicon_handler, FaviconQuery};
+    use crate::state::AppState;
+    use crate::template::OutputForm;
+
+    fn state_with(vars: Vec<(&str, &str)>) -> AppState {
+        let config = Configuration::from_vars(vars).unwrap();
+        AppState::new(config)
     }
 
-    let utc_time = DateTime::<Utc>::from_utc(naive_time.unwrap(), Utc);
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
 
-    // Build context for rendering
-    let context = RenderContext {
-        output_form: OutputForm::Relative,
-        value: utc_time,
-        tz_offset: utc_time.offset().fix(),
-        tz_name: "UTC",
-        view: "basic",
-    };
+    async fn body_json(response: Response) -> serde_json::Value {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
 
-    let rendered_template = render_template(context);
+    #[tokio::test]
+    async fn debug_timing_enabled() {
+        let state = state_with(vec![("DEBUG_ENDPOINTS", "true")]);
+        let query = RenderQuery { debug_timing: Some("1".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
 
-    if rendered_template.is_err() {
-        return Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Full::from(
-                format!("Template Could Not Be Rendered :: {}", rendered_template.err().unwrap())
-            ))
-            .unwrap().into_response();
+        let timings = body_json(response).await;
+        for key in ["parse_us", "render_us", "rasterize_us"] {
+            assert!(timings.get(key).is_some(), "missing timing key {}", key);
+        }
     }
 
-    let rasterize_result = handle_rasterize(rendered_template.unwrap(), extension);
-    match rasterize_result {
-        Ok((mime_type, bytes)) => {
-            (StatusCode::OK, [(header::CONTENT_TYPE, mime_type)], bytes).into_response()
+    #[tokio::test]
+    async fn debug_timing_disabled() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { debug_timing: Some("1".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
+
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn multipart_formats() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { formats: Some("svg,png".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
+
+        let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+        let boundary = content_type.strip_prefix("multipart/mixed; boundary=").unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        // Split the body on the boundary delimiter, dropping the preamble and the closing delimiter
+        let delimiter = format!("--{}", boundary);
+        let mut parts = Vec::new();
+        let mut rest = &body[..];
+        while let Some(start) = find(rest, delimiter.as_bytes()) {
+            rest = &rest[start + delimiter.len()..];
+            if rest.starts_with(b"--") {
+                break;
+            }
+            let end = find(rest, delimiter.as_bytes()).unwrap();
+            parts.push(&rest[..end]);
         }
-        Err(e) => get_error_response(e).into_response()
+        assert_eq!(parts.len(), 2);
+
+        let (svg_headers, svg_body) = split_part(parts[0]);
+        assert!(svg_headers.contains("Content-Type: image/svg+xml"));
+        assert!(std::str::from_utf8(svg_body).unwrap().contains("<svg"));
+
+        let (png_headers, png_body) = split_part(parts[1]);
+        assert!(png_headers.contains("Content-Type: image/x-png"));
+        assert!(png::Decoder::new(png_body).read_info().is_ok());
+    }
+
+    #[tokio::test]
+    async fn multipart_part_limit() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { formats: Some("svg,svg,svg,svg,svg".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
+
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn sri_digest() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { sri: Some("1".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
+
+        let digest = response.headers()["Digest"].to_str().unwrap().to_string();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(digest, format!("sha-256={}", STANDARD.encode(Sha256::digest(&body))));
+
+        // Cached responses carry the digest too, but only when asked for
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
+        assert_eq!(response.headers()["Digest"].to_str().unwrap(), digest);
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &RenderQuery::default());
+        assert!(response.headers().get("Digest").is_none());
+    }
+
+    #[tokio::test]
+    async fn cron_route() {
+        let cron_query = |expr: &str| Query(CronQuery { expr: Some(expr.to_string()), absolute: Some("1".to_string()) });
+        let response = cron_handler(State(state_with(vec![])), cron_query("0 9 * * *"), Query(RenderQuery::default())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(std::str::from_utf8(&body).unwrap().contains("T09:00:00+00:00</text>"));
+
+        let response = cron_handler(State(state_with(vec![])), cron_query("0 9 * *"), Query(RenderQuery::default())).await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_json(response).await["message"].as_str().unwrap().contains("Invalid cron expression '0 9 * *'"));
+
+        let response = cron_handler(State(state_with(vec![])), cron_query("0 0 31 2 *"), Query(RenderQuery::default())).await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn parse_route() {
+        let parse = |input: &str| parse_handler(Query(ParseQuery { input: Some(input.to_string()) }));
+
+        let report = body_json(parse("1686744000").await.into_response()).await;
+        assert_eq!(report["strategy"], "epoch");
+        assert_eq!(report["resolved_iso"], "2023-06-14T12:00:00+00:00");
+        assert_eq!(report["resolved_epoch"], 1686744000);
+
+        let report = body_json(parse("2023-06-14-15-CST").await.into_response()).await;
+        assert_eq!(report["strategy"], "absolute");
+        assert_eq!(report["resolved_iso"], "2023-06-14T21:00:00+00:00");
+
+        assert_eq!(body_json(parse("PT30M").await.into_response()).await["strategy"], "iso8601");
+        assert_eq!(body_json(parse("in 3 days").await.into_response()).await["strategy"], "duration");
+        assert_eq!(parse("banana").await.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn badge_png_is_favicon_sized() {
+        let response = render_time_response(&state_with(vec![]), "-3h", OutputForm::Badge, "png", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        // The IHDR chunk holds the width and height right after the PNG signature
+        assert_eq!(u32::from_be_bytes(body[16..20].try_into().unwrap()), 32);
+        assert_eq!(u32::from_be_bytes(body[20..24].try_into().unwrap()), 32);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn scaled_png() {
+        let query = RenderQuery { scale: Some("2".to_string()), ..Default::default() };
+        let response = render_time_response(&state_with(vec![]), "-3h", OutputForm::Badge, "png", &query);
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        assert_eq!(u32::from_be_bytes(body[16..20].try_into().unwrap()), 64);
+        assert_eq!(u32::from_be_bytes(body[20..24].try_into().unwrap()), 64);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn request_timeout() {
+        // Rendering runs off the async worker, so the timeout can answer while a slow render is still going
+        let app = Router::new()
+            .route("/relative/:path", get(relative_handler))
+            .layer(tower_http::timeout::TimeoutLayer::new(std::time::Duration::from_millis(1)))
+            .with_state(state_with(vec![]));
+        let request = Request::builder().uri("/relative/+1h.gif?frames=60").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn oversized_png() {
+        let state = state_with(vec![]);
+        let oversized = |width: Option<&str>, height: Option<&str>| RenderQuery {
+            width: width.map(str::to_string),
+            height: height.map(str::to_string),
+            dpi: Some("1200".to_string()),
+            scale: Some("4".to_string()),
+            ..Default::default()
+        };
+
+        for query in [oversized(None, None), oversized(Some("4096"), Some("4096"))] {
+            let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "png", &query);
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn opaque_png() {
+        let corner_alpha = |opaque: Option<&str>| {
+            let query = RenderQuery { opaque: opaque.map(str::to_string), ..Default::default() };
+            let response = render_time_response(&state_with(vec![]), "1686744000", OutputForm::Absolute, "png", &query);
+            async move {
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                resvg::tiny_skia::Pixmap::decode_png(&body).unwrap().pixel(0, 0).unwrap().alpha()
+            }
+        };
+
+        assert_eq!(corner_alpha(None).await, 0);
+        assert_eq!(corner_alpha(Some("true")).await, 255);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn webp_output() {
+        let response = render_time_response(&state_with(vec![]), "0", OutputForm::Relative, "webp", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/webp");
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..4], b"RIFF");
+        assert_eq!(&body[8..12], b"WEBP");
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn jpeg_output() {
+        let state = &state_with(vec![]);
+        let jpeg = |query: RenderQuery| async move {
+            let response = render_time_response(state, "1686744000", OutputForm::Absolute, "jpg", &query);
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+            hyper::body::to_bytes(response.into_body()).await.unwrap()
+        };
+
+        let body = jpeg(RenderQuery::default()).await;
+        assert_eq!(&body[..3], b"\xff\xd8\xff");
+        assert!(jpeg(RenderQuery { quality: Some("10".to_string()), ..Default::default() }).await.len() < body.len());
+
+        let query = RenderQuery { quality: Some("best".to_string()), ..Default::default() };
+        let response = render_time_response(state, "1686744000", OutputForm::Absolute, "jpeg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn animated_countdown_gif() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { frames: Some("3".to_string()), exact_seconds: Some("1".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "+30s", OutputForm::Relative, "gif", &query);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/gif");
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..6], b"GIF89a");
+        let mut decoder = gif::DecodeOptions::new().read_info(body.as_ref()).unwrap();
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+
+        let query = RenderQuery { frames: Some("1000".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "+30s", OutputForm::Relative, "gif", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Frames small enough to draw alone are still too many together
+        let query = RenderQuery { dpi: Some("300".to_string()), scale: Some("4".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "+30s", OutputForm::Relative, "gif", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Banners of a fixed time don't change, so they're a single frame
+        let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "gif", &RenderQuery::default());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(body.as_ref()).unwrap();
+        assert!(decoder.read_next_frame().unwrap().is_some());
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn cached_render_identical() {
+        let state = state_with(vec![]);
+        let render = || async {
+            let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "png", &RenderQuery::default());
+            hyper::body::to_bytes(response.into_body()).await.unwrap()
+        };
+
+        let first = render().await;
+        let query = RenderQuery::default();
+        let key = prepare_banner(&state, "1686744000", OutputForm::Absolute, "png", &query).unwrap().cache_key(Utc::now()).unwrap();
+        assert_eq!(state.cache.get(&key).map(|(_, bytes)| bytes), Some(first.clone()));
+        assert_eq!(render().await, first);
+    }
+
+    #[test]
+    fn cache_key_buckets() {
+        let state = state_with(vec![]);
+        let query = RenderQuery::default();
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 10).unwrap();
+        let key = |form, now| prepare_banner(&state, "1686744000", form, "svg", &query).unwrap().cache_key(now);
+
+        // Relative banners are shared within a minute, but not across minutes
+        assert_eq!(key(OutputForm::Relative, now), key(OutputForm::Relative, now + Duration::seconds(40)));
+        assert_ne!(key(OutputForm::Relative, now), key(OutputForm::Relative, now + Duration::seconds(60)));
+        // Absolute banners never change
+        assert_eq!(key(OutputForm::Absolute, now), key(OutputForm::Absolute, now + Duration::days(1)));
+
+        // Unless their time is relative to now
+        let key = |now| prepare_banner(&state, "+1h", OutputForm::Absolute, "svg", &query).unwrap().cache_key(now);
+        assert_eq!(key(now), key(now + Duration::seconds(40)));
+        assert_ne!(key(now), key(now + Duration::seconds(60)));
+
+        // Text isn't cached
+        assert_eq!(prepare_banner(&state, "1686744000", OutputForm::Absolute, "txt", &query).unwrap().cache_key(now), None);
+    }
+
+    #[test]
+    fn cache_key_normalized() {
+        let state = state_with(vec![]);
+        let key = |extension: &str, query: RenderQuery| {
+            prepare_banner(&state, "1686744000", OutputForm::Absolute, extension, &query).unwrap().cache_key(Utc::now()).unwrap()
+        };
+        let plain = key("png", RenderQuery::default());
+
+        // Spellings of the same option share an entry
+        let approx = |value: &str| RenderQuery { approx: Some(value.to_string()), ..Default::default() };
+        assert_eq!(key("png", approx("1")), key("png", approx("true")));
+        let bg = |value: &str| RenderQuery { bg: Some(value.to_string()), ..Default::default() };
+        assert_eq!(key("png", bg("fff")), key("png", bg("#FFFFFF")));
+        let tz = |value: &str| RenderQuery { tz: Some(value.to_string()), ..Default::default() };
+        assert_eq!(key("png", tz("+09:00")), key("png", tz("JST")));
+        assert_eq!(key("jpg", RenderQuery::default()), key("jpeg", RenderQuery::default()));
+
+        // Options that don't change the image don't split it
+        assert_eq!(key("png", RenderQuery { sri: Some("1".to_string()), ..Default::default() }), plain);
+        assert_eq!(key("png", RenderQuery { debug_timing: Some("1".to_string()), ..Default::default() }), plain);
+
+        // Those that do, do
+        assert_ne!(key("png", approx("1")), plain);
+        assert_ne!(key("png", tz("-06:00")), plain);
+        assert_ne!(key("svg", RenderQuery::default()), plain);
+    }
+
+    #[tokio::test]
+    async fn cached_relative_input() {
+        let state = state_with(vec![]);
+        let render = || async {
+            let response = render_time_response(&state, "+1h", OutputForm::Absolute, "svg", &RenderQuery::default());
+            hyper::body::to_bytes(response.into_body()).await.unwrap()
+        };
+
+        let before = Utc::now();
+        let first = render().await;
+        let second = render().await;
+        // Renders either side of a minute boundary are rightly cached apart
+        if before.timestamp().div_euclid(60) != Utc::now().timestamp().div_euclid(60) {
+            return;
+        }
+
+        let query = RenderQuery::default();
+        let key = prepare_banner(&state, "+1h", OutputForm::Absolute, "svg", &query).unwrap().cache_key(before).unwrap();
+        assert_eq!(state.cache.get(&key).map(|(_, bytes)| bytes), Some(first.clone()));
+        assert_eq!(second, first);
+    }
+
+    #[tokio::test]
+    async fn etag_stable() {
+        let etag = |state: &AppState, raw: &str| {
+            let response = render_time_response(state, raw, OutputForm::Absolute, "svg", &RenderQuery::default());
+            response.headers()[header::ETAG].to_str().unwrap().to_string()
+        };
+
+        // Fresh and cached renders of the same banner share a tag, even across processes
+        let first = etag(&state_with(vec![]), "1686744000");
+        assert_eq!(etag(&state_with(vec![]), "1686744000"), first);
+        let state = state_with(vec![]);
+        assert_eq!(etag(&state, "1686744000"), first);
+        assert_eq!(etag(&state, "1686744000"), first);
+
+        assert!(first.starts_with('"') && first.ends_with('"') && first.len() == 34);
+        assert_ne!(etag(&state, "1686744001"), first);
+    }
+
+    #[tokio::test]
+    async fn text_output() {
+        let state = state_with(vec![]);
+        let text = |raw: &str, form: OutputForm| {
+            let response = render_time_response(&state, raw, form, "txt", &RenderQuery::default());
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+            body_text(response)
+        };
+
+        // Rendering happens just after parsing, so an exact hour would already read "in 59 minutes"
+        assert_eq!(text("+1h30m", OutputForm::Relative).await, "in 1 hour");
+        assert_eq!(text("1686744000", OutputForm::Absolute).await, "2023-06-14T12:00:00+00:00");
+        assert_eq!(text("1686744000", OutputForm::BinaryClock).await, "12:00:00");
+
+        // Text can't be cut from an SVG, so it isn't offered alongside images
+        let query = RenderQuery { formats: Some("svg,txt".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &query);
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[tokio::test]
+    async fn fixed_reference_time() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { now: Some("1686744000".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "+1h", OutputForm::Relative, "txt", &query);
+        assert_eq!(body_text(response).await, "in 1 hour");
+        let response = render_time_response(&state, "1686740400", OutputForm::Relative, "txt", &query);
+        assert_eq!(body_text(response).await, "1 hour ago");
+
+        let query = RenderQuery { now: Some("yesterday".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "+1h", OutputForm::Relative, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn localized_relative() {
+        let state = state_with(vec![]);
+        let text = |lang: Option<&str>, raw: &'static str| {
+            let query = RenderQuery { now: Some("1686744000".to_string()), lang: lang.map(str::to_string), ..Default::default() };
+            body_text(render_time_response(&state, raw, OutputForm::Relative, "txt", &query))
+        };
+
+        assert_eq!(text(Some("es"), "+3d").await, "en 3 días");
+        assert_eq!(text(Some("fr"), "+3d").await, "dans 3 jours");
+        assert_eq!(text(Some("fr"), "-3d").await, "il y a 3 jours");
+        assert_eq!(text(None, "+3d").await, "in 3 days");
+        assert_eq!(text(Some("xx"), "+3d").await, "in 3 days");
+    }
+
+    #[tokio::test]
+    async fn custom_colors() {
+        let state = state_with(vec![]);
+        let render = |bg: &str, fg: &str| {
+            let query = RenderQuery { bg: Some(bg.to_string()), fg: Some(fg.to_string()), ..Default::default() };
+            render_time_response(&state, "1686744000", OutputForm::Absolute, "svg", &query)
+        };
+
+        let svg = body_text(render("222222", "#ffffff")).await;
+        assert!(svg.contains("fill=\"#222222\"") && svg.contains("fill=\"#ffffff\""));
+        let svg = body_text(render("#222", "f80")).await;
+        assert!(svg.contains("fill=\"#222222\"") && svg.contains("fill=\"#ff8800\""));
+
+        assert_eq!(render("2222", "fff").status(), StatusCode::BAD_REQUEST);
+        assert_eq!(render("222", "white").status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn font_fallback() {
+        let state = state_with(vec![]);
+        let svg = |font: &str| {
+            let query = RenderQuery { font: Some(font.to_string()), ..Default::default() };
+            body_text(render_time_response(&state, "1686744000", OutputForm::Absolute, "svg", &query))
+        };
+
+        assert!(svg("monospace").await.contains("font-family=\"monospace\""));
+        assert!(svg("No Such Font").await.contains("font-family=\"Roboto Mono\""));
+    }
+
+    #[tokio::test]
+    async fn themed_banner() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { theme: Some("dark".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "svg", &query);
+        assert!(body_text(response).await.contains("fill=\"#0d1117\""));
+
+        let query = RenderQuery { theme: Some("sepia".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn clock_linecap() {
+        let state = state_with(vec![]);
+        let clock = OutputForm::Clock { seconds: false };
+        let query = RenderQuery { linecap: Some("butt".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744000", clock, "svg", &query);
+        assert!(body_text(response).await.contains("stroke-linecap=\"butt\""));
+
+        let query = RenderQuery { linecap: Some("pointy".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744000", clock, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let query = RenderQuery { decimals: Some("9".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744000", clock, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn default_timezone() {
+        let state = state_with(vec![("DEFAULT_TZ", "America/Chicago")]);
+        let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "txt", &RenderQuery::default());
+        assert_eq!(body_text(response).await, "2023-06-14T07:00:00-05:00");
+
+        // Outside daylight saving time
+        let response = render_time_response(&state, "1702555200", OutputForm::Absolute, "txt", &RenderQuery::default());
+        assert_eq!(body_text(response).await, "2023-12-14T06:00:00-06:00");
+
+        let query = RenderQuery { tz: Some("UTC".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744000", OutputForm::Absolute, "txt", &query);
+        assert_eq!(body_text(response).await, "2023-06-14T12:00:00+00:00");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid DEFAULT_TZ")]
+    fn invalid_default_timezone() {
+        state_with(vec![("DEFAULT_TZ", "Mars/Olympus_Mons")]);
+    }
+
+    #[tokio::test]
+    async fn digital_clock() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { tz: Some("+02:30".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1686744045", OutputForm::Digital { seconds: true }, "svg", &query);
+        assert!(body_text(response).await.contains(">14:30:45</text>"));
+        let response = render_time_response(&state, "1686744045", OutputForm::Digital { seconds: false }, "txt", &query);
+        assert_eq!(body_text(response).await, "14:30");
+    }
+
+    #[tokio::test]
+    async fn relative_precision() {
+        let state = state_with(vec![]);
+        let text = |precision: &str| {
+            let query = RenderQuery { now: Some("1686744000".to_string()), precision: Some(precision.to_string()), ..Default::default() };
+            body_text(render_time_response(&state, "+1h5m", OutputForm::Relative, "txt", &query))
+        };
+
+        assert_eq!(text("2").await, "in 1 hour 5 minutes");
+        assert_eq!(text("1").await, "in 1 hour");
+        let query = RenderQuery { precision: Some("lots".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "+1h5m", OutputForm::Relative, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn custom_format() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { format: Some("%Y-%m-%d %H:%M".to_string()), tz: Some("JST".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1752170474", OutputForm::Absolute, "txt", &query);
+        assert_eq!(body_text(response).await, "2025-07-11 03:01");
+
+        let query = RenderQuery { format: Some("%Q".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1752170474", OutputForm::Absolute, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn json_output() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { tz: Some("JST".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "1752170474", OutputForm::Absolute, "json", &query);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let info = body_json(response).await;
+        assert_eq!(info["epoch"], 1752170474);
+        assert_eq!(info["utc"], "2025-07-10T18:01:14+00:00");
+        assert_eq!(info["timezone"], "JST");
+        assert_eq!(info["text"], "2025-07-11T03:01:14+09:00");
+        assert_eq!(info["fields"]["iso"], "2025-07-11T03:01:14+09:00");
+        assert!(info["relative"].as_str().unwrap().ends_with(" ago"));
+    }
+
+    #[tokio::test]
+    async fn cache_control_per_form() {
+        let state = state_with(vec![("CACHE_RELATIVE_MAXAGE", "15"), ("CACHE_ABSOLUTE_MAXAGE", "3600")]);
+        let cache_control = |form| {
+            let response = render_time_response(&state, "1686744000", form, "svg", &RenderQuery::default());
+            assert_eq!(response.status(), StatusCode::OK);
+            response.headers()[header::CACHE_CONTROL].to_str().unwrap().to_string()
+        };
+
+        assert_eq!(cache_control(OutputForm::Relative), "public, max-age=15");
+        assert_eq!(cache_control(OutputForm::Badge), "public, max-age=15");
+        assert_eq!(cache_control(OutputForm::Absolute), "public, max-age=3600, immutable");
+        // Cached renders carry the header too
+        assert_eq!(cache_control(OutputForm::Absolute), "public, max-age=3600, immutable");
+
+        let state = state_with(vec![]);
+        let response = render_time_response(&state, "1686744000", OutputForm::Week, "svg", &RenderQuery::default());
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+        let response = render_time_response(&state, "1686744000", OutputForm::Relative, "svg", &RenderQuery::default());
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=60");
+
+        // Times relative to now go stale in any form
+        for (raw, form) in [("+1h", OutputForm::Absolute), ("PT0S", OutputForm::Clock { seconds: false })] {
+            let response = render_time_response(&state, raw, form, "svg", &RenderQuery::default());
+            assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=60");
+        }
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn favicon_cache_control() {
+        let state = state_with(vec![("CACHE_RELATIVE_MAXAGE", "15")]);
+        for style in ["binary", "clock"] {
+            let favicon_query = FaviconQuery { style: Some(style.to_string()) };
+            let response = favicon_handler(State(state.clone()), Query(favicon_query), Query(RenderQuery::default())).await.into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=15");
+        }
+    }
+
+    #[tokio::test]
+    async fn date_order_query() {
+        let state = state_with(vec![]);
+        let query = RenderQuery { order: Some("MDY".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "06-14-2023", OutputForm::Absolute, "svg", &query);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(std::str::from_utf8(&body).unwrap().contains(">2023-06-14T00:00:00+00:00</text>"));
+
+        // Year first by default, where "06-14-2023" isn't a date
+        let response = render_time_response(&state, "06-14-2023", OutputForm::Absolute, "svg", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let query = RenderQuery { order: Some("YDM".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "2023-06-14", OutputForm::Absolute, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn default_date_order() {
+        let state = state_with(vec![("DEFAULT_DATE_ORDER", "MDY")]);
+        let response = render_time_response(&state, "06-14-2023", OutputForm::Absolute, "txt", &RenderQuery::default());
+        assert_eq!(body_text(response).await, "2023-06-14T00:00:00+00:00");
+
+        let query = RenderQuery { order: Some("DMY".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "14-06-2023", OutputForm::Absolute, "txt", &query);
+        assert_eq!(body_text(response).await, "2023-06-14T00:00:00+00:00");
+        let response = render_time_response(&state, "06-14-2023", OutputForm::Absolute, "txt", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Conversions read dates in the same order
+        let app = Router::new().route("/convert/:path", get(convert_handler)).with_state(state);
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request("/convert/06-14-2023-15?from=JST")).await.unwrap();
+        assert_eq!(body_json(response).await["utc"], "2023-06-14T06:00:00+00:00");
+        let response = app.oneshot(request("/convert/14-06-2023-15?from=JST&order=DMY")).await.unwrap();
+        assert_eq!(body_json(response).await["utc"], "2023-06-14T06:00:00+00:00");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid DEFAULT_DATE_ORDER")]
+    fn invalid_default_date_order() {
+        state_with(vec![("DEFAULT_DATE_ORDER", "YDM")]);
+    }
+
+    #[tokio::test]
+    async fn unknown_extension() {
+        // Lenient by default: unknown extensions fall back to SVG
+        let response = render_time_response(&state_with(vec![]), "0", OutputForm::Absolute, "tiff", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+
+        let strict = state_with(vec![("STRICT_EXTENSIONS", "true")]);
+        let response = render_time_response(&strict, "0", OutputForm::Absolute, "tiff", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = render_time_response(&strict, "0", OutputForm::Absolute, "svg", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn fractional_time_paths() {
+        let state = state_with(vec![]);
+        let headers = HeaderMap::new();
+        let query = RenderQuery::default();
+        assert_eq!(negotiate_path(&state, "+1.5h", &headers, &query), ("+1.5h", "svg", false));
+        assert_eq!(negotiate_path(&state, "PT1.5H", &headers, &query), ("PT1.5H", "svg", false));
+        assert_eq!(negotiate_path(&state, "2023.06.14", &headers, &query), ("2023.06.14", "svg", false));
+        assert_eq!(negotiate_path(&state, "2025-01-17T14:30:00.250Z", &headers, &query), ("2025-01-17T14:30:00.250Z", "svg", false));
+        assert_eq!(negotiate_path(&state, "+1.5h.png", &headers, &query), ("+1.5h", "png", false));
+        assert_eq!(negotiate_path(&state, "0.tiff", &headers, &query), ("0", "tiff", false));
+
+        let app = Router::new()
+            .route("/relative/:path", get(relative_handler))
+            .with_state(state_with(vec![("STRICT_EXTENSIONS", "true")]));
+        let response = app.oneshot(Request::get("/relative/+1.5h?now=1686744000&prefer=txt").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "in 1 hour");
+    }
+
+    #[tokio::test]
+    async fn accept_negotiation() {
+        let chrome = "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8";
+        let request = |uri: &str| Request::get(uri).header(header::ACCEPT, chrome).body(Body::empty()).unwrap();
+        let app = |vars: Vec<(&str, &str)>| Router::new()
+            .route("/relative/:path", get(relative_handler))
+            .with_state(state_with(vars));
+
+        // Extensionless URLs are SVG whatever the browser accepts, and don't vary by Accept
+        let response = app(vec![]).oneshot(request("/relative/+1h")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        assert!(response.headers().get(header::VARY).is_none());
+
+        let accepting = app(vec![("FORMAT_PRECEDENCE", "extension,accept,default")]);
+        let response = accepting.clone().oneshot(request("/relative/+1h")).await.unwrap();
+        assert_eq!(response.headers()[header::VARY], "accept");
+        #[cfg(feature = "raster")]
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/webp");
+        let response = accepting.oneshot(request("/relative/+1h.svg")).await.unwrap();
+        assert!(response.headers().get(header::VARY).is_none());
+    }
+
+    #[tokio::test]
+    async fn garbage_duration_rejected() {
+        let response = render_time_response(&state_with(vec![]), "+banana", OutputForm::Relative, "svg", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn duration_component_error() {
+        let response = render_time_response(&state_with(vec![]), "5h99999999999999m", OutputForm::Relative, "json", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let error = body_json(response).await;
+        assert_eq!(error["message"], "DurationError :: Could not parse minute component '99999999999999'");
+    }
+
+    #[tokio::test]
+    async fn error_banners() {
+        let state = state_with(vec![]);
+        let failed = |extension: &str, query: &RenderQuery| render_time_response(&state, "banana", OutputForm::Relative, extension, query);
+
+        // Images are answered with the error drawn into an image, keeping the status
+        let response = failed("svg", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        let svg = body_text(response).await;
+        assert!(svg.contains("fill=\"#cb2431\"") && svg.contains(">ParserError :: "), "{}", svg);
+
+        // Text formats and multipart requests get JSON
+        let response = failed("json", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_json(response).await["message"].as_str().unwrap().starts_with("ParserError :: "));
+        let response = failed("svg", &RenderQuery { formats: Some("svg,png".to_string()), ..Default::default() });
+        assert_eq!(body_json(response).await["code"], 400);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn png_error_banner() {
+        let response = render_time_response(&state_with(vec![]), "banana", OutputForm::Relative, "png", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/x-png");
+    }
+
+    #[tokio::test]
+    async fn absolute_in_timezone() {
+        let state = state_with(vec![]);
+        let absolute = |tz: Option<&str>| {
+            let query = RenderQuery { tz: tz.map(str::to_string), ..Default::default() };
+            let response = render_time_response(&state, "1752170474", OutputForm::Absolute, "svg", &query);
+            assert_eq!(response.status(), StatusCode::OK);
+            async { String::from_utf8(hyper::body::to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap() }
+        };
+
+        assert!(absolute(Some("CST")).await.contains(">2025-07-10T12:01:14-06:00</text>"));
+        assert!(absolute(Some("+05:30")).await.contains(">2025-07-10T23:31:14+05:30</text>"));
+        assert!(absolute(Some("America/New_York")).await.contains(">2025-07-10T14:01:14-04:00</text>"));
+        assert!(absolute(None).await.contains(">2025-07-10T18:01:14+00:00</text>"));
+        // Unknown zones fall back to UTC
+        assert!(absolute(Some("XYZ")).await.contains(">2025-07-10T18:01:14+00:00</text>"));
+    }
+
+    #[tokio::test]
+    async fn timezone_allowlist() {
+        let state = state_with(vec![("ALLOWED_TIMEZONES", "CST,JST")]);
+
+        let query = RenderQuery { tz: Some("JST".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Week, "svg", &query);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let query = RenderQuery { tz: Some("EST".to_string()), ..Default::default() };
+        let response = render_time_response(&state, "0", OutputForm::Week, "svg", &query);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Conversions are held to the same allowlist
+        let app = Router::new().route("/convert/:path", get(convert_handler)).with_state(state);
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request("/convert/2023-06-14-15?from=CST&to=JST")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request("/convert/2023-06-14-15?from=EST&to=JST")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app.oneshot(request("/convert/2023-06-14-15?from=CST&to=EST")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "raster")]
+    #[tokio::test]
+    async fn readiness_healthy() {
+        let response = readiness_handler(State(state_with(vec![]))).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let readiness = body_json(response).await;
+        assert_eq!(readiness["ready"], true);
+        assert!(readiness["templates"].as_array().unwrap().contains(&"basic.svg".into()));
+    }
+
+    #[cfg(not(feature = "raster"))]
+    #[tokio::test]
+    async fn svg_only_build() {
+        let state = state_with(vec![]);
+
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "svg", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+
+        let response = render_time_response(&state, "0", OutputForm::Absolute, "png", &RenderQuery::default());
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+
+        let response = readiness_handler(State(state)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["fonts"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn health_routes() {
+        let app = Router::new()
+            .route("/healthz", get(liveness_handler))
+            .route("/readyz", get(readiness_handler))
+            .route("/:path", get(implicit_handler))
+            .with_state(state_with(vec![]));
+        let get_path = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        // Neither is mistaken for a time by the catch-all route
+        let response = app.clone().oneshot(get_path("/healthz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["alive"], true);
+
+        let response = app.oneshot(get_path("/readyz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["ready"], true);
+    }
+
+    #[tokio::test]
+    async fn request_metrics() {
+        let app = Router::new()
+            .route("/metrics", get(metrics_handler))
+            .route("/relative/:path", get(relative_handler))
+            .route("/:path", get(implicit_handler))
+            .with_state(state_with(vec![]));
+        let get_path = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(get_path("/relative/1686744000.svg")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(get_path("/metrics")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/plain"));
+        let metrics = body_text(response).await;
+        assert!(metrics.lines().any(|line| line == "time_banner_requests_total{route=\"relative\"} 1"));
+        assert!(metrics.contains("time_banner_rasterize_seconds_count 1"));
+        assert!(!metrics.contains("route=\"implicit\""));
+    }
+
+    #[tokio::test]
+    async fn readiness_without_fonts() {
+        let response = Readiness::new(vec!["basic.svg".to_string()], Some(0)).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = Readiness::new(vec![], Some(3)).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn templates_listing() {
+        let response = templates_handler(State(state_with(vec![("DEBUG_ENDPOINTS", "true")]))).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let listing = body_json(response).await;
+        let names: Vec<&str> = listing.as_array().unwrap().iter()
+            .map(|template| template["name"].as_str().unwrap())
+            .collect();
+        assert!(names.contains(&"basic.svg"));
+        assert!(names.contains(&"binary.svg"));
+        let basic = listing.as_array().unwrap().iter().find(|template| template["name"] == "basic.svg").unwrap();
+        assert_eq!(basic["preview"].as_str().map(|p| p.starts_with("/relative/")), Some(true));
+    }
+
+    #[tokio::test]
+    async fn templates_listing_disabled() {
+        let response = templates_handler(State(state_with(vec![]))).await.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn diff_route() {
+        let path = Path(("0".to_string(), "183600.svg".to_string()));
+        let query = DiffQuery { unit: Some("hours".to_string()), decimals: None };
+        let response = diff_handler(State(state_with(vec![])), path, Query(query)).await.into_response();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(std::str::from_utf8(&body).unwrap().contains(">51 hours</text>"));
+
+        let path = Path(("0".to_string(), "181440".to_string()));
+        let query = DiffQuery { unit: Some("days".to_string()), decimals: Some("1".to_string()) };
+        let response = diff_handler(State(state_with(vec![])), path, Query(query)).await.into_response();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(std::str::from_utf8(&body).unwrap().contains(">2.1 days</text>"));
+
+        let path = Path(("0".to_string(), "60".to_string()));
+        let query = DiffQuery { unit: Some("eons".to_string()), decimals: None };
+        assert_eq!(diff_handler(State(state_with(vec![])), path, Query(query)).await.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn sparkline_query(epochs: &[&str]) -> Query<Vec<(String, String)>> {
+        Query(epochs.iter().map(|epoch| ("t".to_string(), epoch.to_string())).collect())
+    }
+
+    #[tokio::test]
+    async fn sparkline_points_plotted() {
+        let response = sparkline_handler(sparkline_query(&["0", "3600", "86400", "90000", "172800"])).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(std::str::from_utf8(&body).unwrap().matches("<circle").count(), 5);
+    }
+
+    #[tokio::test]
+    async fn sparkline_validation() {
+        let response = sparkline_handler(sparkline_query(&["0", "soon"])).await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = sparkline_handler(sparkline_query(&[])).await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let many: Vec<String> = (0..65).map(|i| i.to_string()).collect();
+        let many: Vec<&str> = many.iter().map(String::as_str).collect();
+        let response = sparkline_handler(sparkline_query(&many)).await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "raster")]
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    /// Split a multipart part into its headers and its body (without the trailing CRLF).
+    #[cfg(feature = "raster")]
+    fn split_part(part: &[u8]) -> (String, &[u8]) {
+        let separator = find(part, b"\r\n\r\n").unwrap();
+        let headers = String::from_utf8(part[..separator].to_vec()).unwrap();
+        (headers, &part[separator + 4..part.len() - 2])
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use chrono::Utc;
+
+use crate::blocklist::Blocklist;
+use crate::cache::RenderCache;
+use crate::headers::SecurityHeaders;
+use crate::metrics::Metrics;
+use crate::negotiate::FormatPrecedence;
+use crate::parse::{DateSegmentOrder, parse_timezone_at};
+#[cfg(feature = "raster")]
+use crate::raster::Rasterizer;
+use crate::config::Configuration;
+
+/// Cached render output: the MIME type and the encoded body.
+pub type CachedRender = (&'static str, Bytes);
+
+/// Shared state available to every handler.
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Configuration>,
+    pub cache: Arc<RenderCache<String, CachedRender>>,
+    pub blocklist: Arc<Blocklist>,
+    #[cfg(feature = "raster")]
+    pub rasterizer: Arc<Rasterizer>,
+    pub security_headers: Arc<SecurityHeaders>,
+    pub format_precedence: Arc<FormatPrecedence>,
+    pub metrics: Arc<Metrics>,
+    /// Segment order of numeric dates, unless a request gives its own.
+    pub date_order: DateSegmentOrder,
+}
+
+impl AppState {
+    pub fn new(config: Configuration) -> Self {
+        if let Some(tz) = &config.default_tz {
+            parse_timezone_at(tz, Utc::now()).expect("Invalid DEFAULT_TZ");
+        }
+
+        Self {
+            cache: Arc::new(RenderCache::new(config.cache_capacity, Duration::from_secs(config.cache_ttl))),
+            blocklist: Arc::new(Blocklist::from_config(&config).expect("Invalid blocklist configuration")),
+            #[cfg(feature = "raster")]
+            rasterizer: Rasterizer::shared(),
+            security_headers: Arc::new(SecurityHeaders::from_config(&config).expect("Invalid security header configuration")),
+            format_precedence: Arc::new(FormatPrecedence::from_config(&config).expect("Invalid FORMAT_PRECEDENCE")),
+            metrics: Arc::new(Metrics::default()),
+            date_order: config.default_date_order.as_deref().map(str::parse).transpose().expect("Invalid DEFAULT_DATE_ORDER").unwrap_or_default(),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Font faces available for rasterizing.
+    #[cfg(feature = "raster")]
+    pub fn font_count(&self) -> Option<usize> {
+        Some(self.rasterizer.font_count())
+    }
+
+    /// Builds without the `raster` feature never rasterize, so they have no fonts to report.
+    #[cfg(not(feature = "raster"))]
+    pub fn font_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether a font family can be drawn with.
+    #[cfg(feature = "raster")]
+    pub fn has_font_family(&self, family: &str) -> bool {
+        self.rasterizer.has_font_family(family)
+    }
+
+    /// Without the `raster` feature there's no font database, so only generic families are offered.
+    #[cfg(not(feature = "raster"))]
+    pub fn has_font_family(&self, _family: &str) -> bool {
+        false
+    }
+}
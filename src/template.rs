@@ -1,47 +1,1400 @@
-use chrono::{DateTime, FixedOffset, Utc};
-use timeago::Formatter;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, SecondsFormat, TimeZone, Timelike, Utc};
+use chrono::format::{Item, StrftimeItems};
+use serde::Serialize;
+use timeago::{Formatter, TimeUnit};
 use tera::{Context, Tera};
-use lazy_static::lazy_static;
-
-lazy_static! {
-    static ref TEMPLATES: Tera = {
-        let mut _tera = match Tera::new("templates/**/*.svg") {
-            Ok(t) => {
-                let names: Vec<&str> = t.get_template_names().collect();
-                println!("{} templates found ([{}]).", names.len(), names.join(", "));
-                t
-            },
-            Err(e) => {
-                println!("Parsing error(s): {}", e);
-                ::std::process::exit(1);
-            }
-        };
-        _tera
-    };
+
+use crate::color::{DARK_BACKGROUND, DARK_TEXT, Rgb, Theme};
+use crate::locale::Locale;
+
+/// Where templates are loaded from when `TEMPLATE_DIR` isn't set.
+/// Development builds run from the repository root, release builds next to the copied templates.
+pub const DEFAULT_TEMPLATE_DIR: &str = if cfg!(debug_assertions) { "src/templates" } else { "templates" };
+
+/// The templates every render uses, loaded by `init_templates` at startup.
+static TEMPLATES: OnceLock<Tera> = OnceLock::new();
+
+/// Load the templates in `dir` for every later render, returning how many were found.
+/// Called at startup so a broken template directory stops the server before it accepts requests.
+pub fn init_templates(dir: &str) -> Result<usize, String> {
+    let tera = load_templates(dir)?;
+    let names: Vec<&str> = tera.get_template_names().collect();
+    tracing::debug!("{} templates found in {} ([{}]).", names.len(), dir, names.join(", "));
+    let count = names.len();
+
+    TEMPLATES.set(tera).map_err(|_| "Templates were already loaded".to_string())?;
+    Ok(count)
+}
+
+/// The loaded templates. Without `init_templates` (as in tests), they're loaded from the default directory on first use.
+fn templates() -> &'static Tera {
+    TEMPLATES.get_or_init(|| load_templates(DEFAULT_TEMPLATE_DIR).unwrap_or_else(|e| panic!("{}", e)))
 }
 
+/// Parse every SVG template under a directory, failing if any is malformed or there are none.
+pub fn load_templates(dir: &str) -> Result<Tera, String> {
+    let mut tera = Tera::new(&format!("{}/**/*.svg", dir.trim_end_matches('/')))
+        .map_err(|e| format!("Failed to load templates from {}: {:?}", dir, e))?;
+    if tera.get_template_names().next().is_none() {
+        return Err(format!("No templates found in {}", dir));
+    }
+
+    // Rendered values are user-influenced, so escape them for the SVG (XML) output
+    tera.autoescape_on(vec![".svg"]);
+    Ok(tera)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OutputForm {
     Relative,
     Absolute,
+    Epoch { grouped: bool },
+    Week,
+    BinaryClock,
+    /// A digital clock readout, like "14:30:45", optionally without the seconds.
+    Digital { seconds: bool },
+    /// A favicon-sized analog clock face, optionally with a seconds hand.
+    Clock { seconds: bool },
+    /// A favicon-sized square with a compact relative time, like "3h".
+    Badge,
+    Iso(IsoForm),
+}
+
+/// ISO 8601 representations of a time, rendered by `/iso`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IsoForm {
+    /// Year and day of the year, like "2023-165".
+    Ordinal,
+    /// ISO week-numbering year, week and weekday, like "2023-W24-3".
+    Week,
+    /// The date and time without separators, like "20230614T154530Z".
+    #[default]
+    Basic,
+}
+
+impl FromStr for IsoForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ordinal" => Ok(IsoForm::Ordinal),
+            "week" => Ok(IsoForm::Week),
+            "basic" => Ok(IsoForm::Basic),
+            _ => Err(format!("Unknown ISO 8601 form: {}", s)),
+        }
+    }
 }
 
+impl OutputForm {
+    /// The template used to render this form.
+    pub fn view(&self) -> &'static str {
+        match self {
+            OutputForm::BinaryClock => "binary",
+            OutputForm::Digital { .. } => "digital",
+            OutputForm::Clock { .. } => "clock",
+            OutputForm::Badge => "badge",
+            _ => "basic",
+        }
+    }
+
+    /// Whether the rendered banner goes stale as time passes, rather than always showing the same time.
+    pub fn changes_over_time(&self) -> bool {
+        matches!(self, OutputForm::Relative | OutputForm::Badge)
+    }
+}
+
+#[derive(Clone)]
 pub struct RenderContext<'a> {
     pub output_form: OutputForm,
     pub value: DateTime<Utc>,
     pub tz_offset: FixedOffset,
+    #[allow(dead_code)]
     pub tz_name: &'a str,
     pub view: &'a str,
+    pub approximate: bool,
+    /// Give relative times under a minute to the exact second.
+    pub exact_seconds: bool,
+    pub prefix: Option<&'a str>,
+    pub suffix: Option<&'a str>,
+    /// When set, the SVG is sized in physical units (inches) for printing at this resolution.
+    pub dpi: Option<f32>,
+    pub background: Option<Rgb>,
+    pub foreground: Option<Rgb>,
+    /// Degrees to rotate the banner clockwise about its center.
+    pub rotate: f32,
+    /// Append "(as of <generation time>)" to absolute banners.
+    pub as_of: bool,
+    /// A `strftime` format for absolute banners, in place of RFC 3339. Must have passed `validate_format`.
+    pub format: Option<&'a str>,
+    /// The Unix epoch relative times are measured from, in place of the current time.
+    pub now: Option<i64>,
+    /// The language relative times are written in.
+    pub locale: Locale,
+    /// The most units relative times are given in, from 1 ("1 hour ago") to `MAX_PRECISION`.
+    pub precision: usize,
+    /// Colors for anything without an explicit `background` or `foreground`.
+    pub theme: Theme,
+    /// The font family text is drawn in, as chosen by `resolve_font`.
+    pub font: &'a str,
+    /// Fixed dimensions for the basic banner, in place of fitting it to the text.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// How the analog clock's hands and ticks end.
+    pub linecap: LineCap,
+    /// Decimal places the analog clock's coordinates are rounded to, up to `MAX_CLOCK_DECIMALS`.
+    pub clock_decimals: usize,
+}
+
+impl RenderContext<'_> {
+    /// The time relative banners are measured from: the `now` override if given, or the current time.
+    pub fn reference_time(&self) -> DateTime<Utc> {
+        self.now.and_then(|now| Utc.timestamp_opt(now, 0).single()).unwrap_or_else(Utc::now)
+    }
+}
+
+/// The size of a template's coordinate space (its `viewBox`), in pixels at 96 DPI.
+fn view_size(view: &str) -> (u32, u32) {
+    match view {
+        "binary" | "badge" | "clock" => (32, 32),
+        "digital" => (160, 44),
+        "sparkline" => (SPARKLINE_WIDTH as u32, 20),
+        _ => (512, 34),
+    }
+}
+
+/// The basic banner's font size, and the space left on either side of its text.
+const BASIC_FONT_SIZE: f32 = 27.0;
+const BASIC_PADDING: f32 = 8.0;
+
+/// Accepted range for a requested banner width or height, in pixels.
+pub const MIN_DIMENSION: u32 = 16;
+pub const MAX_DIMENSION: u32 = 4096;
+
+/// Parse a requested banner width or height, rejecting values outside of the accepted range.
+pub fn parse_dimension(raw: &str) -> Result<u32, String> {
+    match raw.parse::<u32>() {
+        Ok(pixels) if (MIN_DIMENSION..=MAX_DIMENSION).contains(&pixels) => Ok(pixels),
+        _ => Err(format!("Dimensions must be between {} and {} pixels", MIN_DIMENSION, MAX_DIMENSION)),
+    }
+}
+
+/// The width the basic banner needs to fit its text, assuming monospace glyphs 0.6em wide.
+/// Banners are never narrower than the template's own width, so short text keeps the familiar size.
+fn fitted_width(text: &str) -> u32 {
+    let (width, _) = view_size("basic");
+    let needed = text.chars().count() as f32 * 0.6 * BASIC_FONT_SIZE + 2.0 * BASIC_PADDING;
+    width.max(needed.ceil() as u32)
+}
+
+/// The size of a banner's coordinate space: the basic banner is sized to its text unless a width or height is
+/// given, while other templates are drawn at a fixed size.
+fn banner_size(context: &RenderContext, text: &str) -> (u32, u32) {
+    let (width, height) = view_size(context.view);
+    if context.view != "basic" {
+        return (width, height);
+    }
+
+    (context.width.unwrap_or_else(|| fitted_width(text)), context.height.unwrap_or(height))
+}
+
+/// Parse a rotation in degrees, normalized into [0, 360).
+pub fn parse_rotation(raw: &str) -> Result<f32, String> {
+    match raw.parse::<f32>() {
+        Ok(degrees) if degrees.is_finite() => Ok(degrees.rem_euclid(360.0)),
+        _ => Err(format!("Invalid rotation: {}", raw)),
+    }
+}
+
+/// The bounding box of a width x height rectangle rotated about its center.
+fn rotated_bounds(width: f32, height: f32, degrees: f32) -> (f32, f32) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    // Round away floating point noise, so right angles swap the dimensions exactly
+    let round = |value: f32| (value * 100.0).round() / 100.0;
+    (round(width * cos.abs() + height * sin.abs()), round(width * sin.abs() + height * cos.abs()))
+}
+
+/// Maximum length, in characters, of a prefix or suffix label.
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// Check that a prefix or suffix label is short and free of control characters.
+/// Markup in the label is left alone here, as the template escapes it.
+pub fn validate_label(label: &str) -> Result<&str, String> {
+    if label.chars().count() > MAX_LABEL_LENGTH {
+        return Err(format!("Labels may be at most {} characters", MAX_LABEL_LENGTH));
+    }
+    if label.chars().any(char::is_control) {
+        return Err("Labels may not contain control characters".to_string());
+    }
+
+    Ok(label)
+}
+
+/// Maximum length, in characters, of a custom date format.
+pub const MAX_FORMAT_LENGTH: usize = 64;
+
+/// Check that a custom date format is short and can be displayed.
+/// chrono only reports bad specifiers (like "%Q") while formatting, by panicking, so they're looked for here first.
+pub fn validate_format(format: &str) -> Result<&str, String> {
+    if format.chars().count() > MAX_FORMAT_LENGTH {
+        return Err(format!("Formats may be at most {} characters", MAX_FORMAT_LENGTH));
+    }
+    if format.chars().any(char::is_control) {
+        return Err("Formats may not contain control characters".to_string());
+    }
+
+    // "%#z" is only meant for parsing, and panics when formatted
+    let parse_only = StrftimeItems::new("%#z").next();
+    if StrftimeItems::new(format).any(|item| item == Item::Error || Some(&item) == parse_only.as_ref()) {
+        return Err(format!("Invalid format: {}", format));
+    }
+
+    Ok(format)
+}
+
+/// The font family banners are drawn in, unless another is requested.
+pub const DEFAULT_FONT: &str = "Roboto Mono";
+
+/// CSS generic font families, which always resolve to some installed font.
+const GENERIC_FONTS: [&str; 5] = ["serif", "sans-serif", "monospace", "cursive", "fantasy"];
+
+/// Choose the font family for a banner: the requested family if it's generic or `available`, otherwise the default.
+/// Unusable fonts fall back rather than failing, as the banner is still readable without them.
+pub fn resolve_font(requested: &str, available: impl Fn(&str) -> bool) -> &str {
+    let well_formed = !requested.is_empty() && requested.len() <= MAX_LABEL_LENGTH
+        && requested.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-');
+    if well_formed && (GENERIC_FONTS.contains(&requested.to_lowercase().as_str()) || available(requested)) {
+        requested
+    } else {
+        DEFAULT_FONT
+    }
+}
+
+/// The names of all loaded templates.
+pub fn template_names() -> Vec<String> {
+    templates().get_template_names().map(str::to_string).collect()
+}
+
+/// Format an epoch as a string, optionally grouping digits into thousands with commas.
+pub fn format_epoch(epoch: i64, grouped: bool) -> String {
+    let digits = epoch.unsigned_abs().to_string();
+    let sign = if epoch < 0 { "-" } else { "" };
+    if !grouped {
+        return format!("{}{}", sign, digits);
+    }
+
+    // Commas are placed wherever the remaining digit count is a multiple of three
+    let offset = digits.len() % 3;
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && i % 3 == offset {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+
+    format!("{}{}", sign, result)
+}
+
+/// Format the ISO week of the time (as seen in the given offset), like "2024-W03".
+/// The year is the ISO week-numbering year, which can differ from the calendar year near January 1st.
+pub fn format_iso_week(value: DateTime<Utc>, offset: FixedOffset) -> String {
+    let week = value.with_timezone(&offset).iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// Format the time (as seen in the given offset) in one of the ISO 8601 representations.
+/// Basic times in UTC end in "Z"; other offsets are written in basic form too, like "+0900".
+pub fn format_iso(value: DateTime<Utc>, offset: FixedOffset, form: IsoForm) -> String {
+    let local = value.with_timezone(&offset);
+    let format = match form {
+        IsoForm::Ordinal => "%Y-%j",
+        IsoForm::Week => "%G-W%V-%u",
+        IsoForm::Basic if offset.local_minus_utc() == 0 => "%Y%m%dT%H%M%SZ",
+        IsoForm::Basic => "%Y%m%dT%H%M%S%z",
+    };
+
+    local.format(format).to_string()
+}
+
+/// A single dot of the binary clock, positioned within the 32x32 favicon.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct BinaryDot {
+    pub x: f32,
+    pub y: f32,
+    pub lit: bool,
+}
+
+/// Lay out a BCD binary clock: six columns (tens and ones of hours, minutes and seconds),
+/// each four bits tall with the most significant bit at the top.
+pub fn binary_clock_dots(hour: u32, minute: u32, second: u32) -> Vec<BinaryDot> {
+    let digits = [hour / 10, hour % 10, minute / 10, minute % 10, second / 10, second % 10];
+
+    digits.iter().enumerate().flat_map(|(column, digit)| {
+        (0..4).map(move |row| BinaryDot {
+            x: 3.5 + column as f32 * 5.0,
+            y: 5.5 + row as f32 * 7.0,
+            lit: digit & (1 << (3 - row)) != 0,
+        })
+    }).collect()
+}
+
+/// A point within the 32x32 analog clock face. Kept as `f64` so rounded coordinates print as written,
+/// rather than as the widened `f32` (16.58 becoming 16.579999923706055).
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub struct ClockPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// The ends of the analog clock's hands, which all start at the center.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ClockHands {
+    pub hour: ClockPoint,
+    pub minute: ClockPoint,
+    pub second: ClockPoint,
+}
+
+/// A tick mark on the edge of the analog clock face.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ClockTick {
+    pub from: ClockPoint,
+    pub to: ClockPoint,
+}
+
+/// How the ends of the analog clock's hands and ticks are drawn, as an SVG `stroke-linecap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// Rounded past the end of the line.
+    #[default]
+    Round,
+    /// Cut flat at the end of the line.
+    Butt,
+    /// Squared off past the end of the line.
+    Square,
+}
+
+impl FromStr for LineCap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "round" => Ok(LineCap::Round),
+            "butt" => Ok(LineCap::Butt),
+            "square" => Ok(LineCap::Square),
+            _ => Err(format!("Unknown line cap (expected round, butt or square): {}", s)),
+        }
+    }
+}
+
+impl LineCap {
+    /// The `stroke-linecap` attribute value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineCap::Round => "round",
+            LineCap::Butt => "butt",
+            LineCap::Square => "square",
+        }
+    }
+}
+
+const CLOCK_CENTER: f64 = 16.0;
+
+/// Decimal places the analog clock's coordinates are rounded to by default, and the most that may be requested.
+/// Each place costs a byte per coordinate; on a 32x32 face one place is still a tenth of a pixel, so favicons look the same
+/// with `?decimals=1` while larger renders may show slightly uneven hand angles.
+pub const DEFAULT_CLOCK_DECIMALS: usize = 2;
+pub const MAX_CLOCK_DECIMALS: usize = 4;
+
+/// Parse a requested number of decimal places for the analog clock's coordinates.
+pub fn parse_clock_decimals(raw: &str) -> Result<usize, String> {
+    match raw.parse::<usize>() {
+        Ok(decimals) if decimals <= MAX_CLOCK_DECIMALS => Ok(decimals),
+        _ => Err(format!("Decimals must be between 0 and {}", MAX_CLOCK_DECIMALS)),
+    }
+}
+
+/// The point `length` away from the clock's center, `degrees` clockwise from 12 o'clock, rounded to `decimals` places.
+fn clock_point(degrees: f64, length: f64, decimals: usize) -> ClockPoint {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let factor = 10f64.powi(decimals as i32);
+    let round = |value: f64| (value * factor).round() / factor;
+    ClockPoint { x: round(CLOCK_CENTER + length * sin), y: round(CLOCK_CENTER - length * cos) }
+}
+
+/// Place the analog clock's hands. The hour and minute hands move continuously, while the second hand ticks.
+pub fn calculate_clock_hands(hour: u32, minute: u32, second: u32, decimals: usize) -> ClockHands {
+    let minutes = minute as f64 + second as f64 / 60.0;
+    ClockHands {
+        hour: clock_point((hour % 12) as f64 * 30.0 + minutes / 2.0, 7.0, decimals),
+        minute: clock_point(minutes * 6.0, 11.0, decimals),
+        second: clock_point(second as f64 * 6.0, 12.0, decimals),
+    }
+}
+
+/// The twelve hour marks around the clock face, starting at 12 o'clock.
+pub fn clock_ticks(decimals: usize) -> Vec<ClockTick> {
+    (0..12).map(|hour| {
+        let degrees = hour as f64 * 30.0;
+        ClockTick { from: clock_point(degrees, 12.5, decimals), to: clock_point(degrees, 14.0, decimals) }
+    }).collect()
+}
+
+/// Maximum number of times plotted on a single sparkline.
+pub const MAX_SPARKLINE_POINTS: usize = 64;
+
+const SPARKLINE_WIDTH: f32 = 120.0;
+const SPARKLINE_PADDING: f32 = 4.0;
+
+/// A single plotted time on a sparkline.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SparkPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Place each epoch along a horizontal timeline, scaled between the earliest and latest of them.
+/// When every epoch is the same, the points sit in the middle.
+pub fn sparkline_points(epochs: &[i64]) -> Vec<SparkPoint> {
+    let (min, max) = match (epochs.iter().min(), epochs.iter().max()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return Vec::new(),
+    };
+    let span = (max - min) as f64;
+    let usable = (SPARKLINE_WIDTH - SPARKLINE_PADDING * 2.0) as f64;
+
+    epochs.iter().map(|epoch| SparkPoint {
+        x: if span == 0.0 {
+            SPARKLINE_WIDTH / 2.0
+        } else {
+            SPARKLINE_PADDING + ((epoch - min) as f64 / span * usable) as f32
+        },
+        y: 10.0,
+    }).collect()
+}
+
+/// Render a sparkline of the given epochs.
+pub fn render_sparkline(epochs: &[i64]) -> Result<String, tera::Error> {
+    let mut points = sparkline_points(epochs);
+    // The baseline runs from the earliest to the latest point
+    points.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+    let (width, height) = view_size("sparkline");
+    let mut template_context = Context::new();
+    template_context.insert("points", &points);
+    template_context.insert("width", &width);
+    template_context.insert("height", &height);
+
+    templates().render("sparkline.svg", &template_context)
+}
+
+/// A single unit a time difference can be expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffUnit {
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
+}
+
+impl FromStr for DiffUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim_end_matches('s') {
+            "second" => Ok(DiffUnit::Seconds),
+            "minute" => Ok(DiffUnit::Minutes),
+            "hour" => Ok(DiffUnit::Hours),
+            "day" => Ok(DiffUnit::Days),
+            "week" => Ok(DiffUnit::Weeks),
+            _ => Err(format!("Unknown unit: {}", s)),
+        }
+    }
+}
+
+impl DiffUnit {
+    fn seconds(&self) -> i64 {
+        match self {
+            DiffUnit::Seconds => 1,
+            DiffUnit::Minutes => 60,
+            DiffUnit::Hours => 3600,
+            DiffUnit::Days => 86400,
+            DiffUnit::Weeks => 604800,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            DiffUnit::Seconds => "second",
+            DiffUnit::Minutes => "minute",
+            DiffUnit::Hours => "hour",
+            DiffUnit::Days => "day",
+            DiffUnit::Weeks => "week",
+        }
+    }
+}
+
+/// Maximum number of decimal places a single-unit difference may be shown with.
+pub const MAX_DIFF_DECIMALS: usize = 6;
+
+/// Describe the time between two values. Without a unit this is a span like "2 days 3 hours";
+/// with one it is the total in that unit, truncated to a whole number unless decimals are requested ("2.1 days").
+pub fn format_difference(a: DateTime<Utc>, b: DateTime<Utc>, unit: Option<DiffUnit>, decimals: Option<usize>) -> String {
+    let seconds = (b - a).num_seconds().abs();
+
+    let unit = match unit {
+        Some(unit) => unit,
+        None => {
+            let mut formatter = Formatter::new();
+            formatter.num_items(2).ago("");
+            return formatter.convert(std::time::Duration::from_secs(seconds as u64)).trim().to_string();
+        }
+    };
+
+    let (value, singular) = match decimals {
+        Some(decimals) => {
+            let value = seconds as f64 / unit.seconds() as f64;
+            (format!("{:.*}", decimals, value), decimals == 0 && value.round() == 1.0)
+        }
+        None => {
+            let value = seconds / unit.seconds();
+            (value.to_string(), value == 1)
+        }
+    };
+
+    format!("{} {}{}", value, unit.label(), if singular { "" } else { "s" })
+}
+
+/// Render an error message into a red banner, shown in place of an image that couldn't be made.
+pub fn render_error(message: &str) -> Result<String, tera::Error> {
+    let (width, height) = (fitted_width(message), view_size("error").1);
+    let mut template_context = Context::new();
+    template_context.insert("text", message);
+    template_context.insert("font_family", DEFAULT_FONT);
+    template_context.insert("width", &width);
+    template_context.insert("height", &height);
+    template_context.insert("view_box", &format!("0 0 {} {}", width, height));
+
+    templates().render("error.svg", &template_context)
+}
+
+/// The basic banner's text baseline, which keeps the text vertically centered in a banner of the given height.
+fn basic_text_y(height: u32) -> f32 {
+    (height as f32 + 20.0) / 2.0
+}
+
+/// Render plain text into the basic banner.
+pub fn render_text(text: &str) -> Result<String, tera::Error> {
+    let (width, height) = (fitted_width(text), view_size("basic").1);
+    let mut template_context = Context::new();
+    template_context.insert("text", text);
+    template_context.insert("font_family", DEFAULT_FONT);
+    template_context.insert("width", &width);
+    template_context.insert("height", &height);
+    template_context.insert("view_box", &format!("0 0 {} {}", width, height));
+    template_context.insert("view_width", &width);
+    template_context.insert("view_height", &height);
+    template_context.insert("text_y", &basic_text_y(height));
+
+    templates().render("basic.svg", &template_context)
+}
+
+/// Round an elapsed duration for approximate phrasing: to the nearest 5 minutes under an hour,
+/// the nearest hour under a day, and the nearest day beyond that.
+fn approximate_duration(elapsed: std::time::Duration) -> std::time::Duration {
+    let seconds = elapsed.as_secs();
+    let step = match seconds {
+        s if s < 3600 => 300,
+        s if s < 86400 => 3600,
+        _ => 86400,
+    };
+
+    std::time::Duration::from_secs((seconds + step / 2) / step * step)
+}
+
+/// Describe the time between `value` and `now` in a few characters, like "3h" or "2d", for badges.
+/// Only the largest whole unit is shown, and the direction is left out.
+pub fn format_compact_relative(value: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - value).num_seconds().unsigned_abs();
+    let units = [(31_557_600, "y"), (604_800, "w"), (86_400, "d"), (3_600, "h"), (60, "m")];
+    let (unit_seconds, suffix) = units.into_iter().find(|(unit, _)| seconds >= *unit).unwrap_or((1, "s"));
+
+    format!("{}{}", seconds / unit_seconds, suffix)
+}
+
+/// The largest font size (up to 20px) at which text fits across a 32px badge, assuming monospace glyphs 0.6em wide.
+fn badge_font_size(text: &str) -> f64 {
+    let width = 0.6 * text.chars().count().max(1) as f64;
+    ((28.0 / width).min(20.0) * 10.0).floor() / 10.0
+}
+
+/// The most units a relative time may be given in, as in "1 year 2 months 3 days 4 hours 5 minutes 6 seconds".
+pub const MAX_PRECISION: usize = 7;
+
+/// Parse a requested number of units for relative times; out of range values are clamped to 1 to `MAX_PRECISION`.
+pub fn parse_precision(raw: &str) -> Result<usize, String> {
+    let precision = raw.parse::<i64>().map_err(|_| format!("Invalid precision: {}", raw))?;
+    Ok(precision.clamp(1, MAX_PRECISION as i64) as usize)
+}
+
+/// Describe the time relative to `now`, like "3 hours ago", or "in 3 hours" for future times.
+/// In approximate mode the duration is rounded more aggressively and prefixed with "about".
+/// With `exact_seconds`, times less than a minute away are always given to the second ("8 seconds ago"),
+/// never rounded or reduced to "now".
+pub fn format_relative(value: DateTime<Utc>, now: DateTime<Utc>, approximate: bool, exact_seconds: bool) -> String {
+    format_relative_in(value, now, approximate, exact_seconds, Locale::English, 1)
+}
+
+/// Describe the time relative to `now` like `format_relative`, in the given language and with up to `precision`
+/// units ("1 hour 5 minutes ago").
+pub fn format_relative_in(value: DateTime<Utc>, now: DateTime<Utc>, approximate: bool, exact_seconds: bool, locale: Locale, precision: usize) -> String {
+    let mut formatter = Formatter::with_language(locale.language());
+    // timeago only describes the past, so durations are phrased without a direction and placed afterwards
+    formatter.ago("").num_items(precision);
+    let future = value - now >= Duration::seconds(1);
+    let elapsed = if future { value - now } else { now - value }.to_std().unwrap_or_default();
+    let directed = |phrase: String| if future { locale.future(&phrase) } else { locale.past(&phrase) };
+
+    if exact_seconds && elapsed < std::time::Duration::from_secs(60) {
+        // A "0" placeholder makes timeago write "0 seconds" instead of "now"
+        return directed(formatter.min_unit(TimeUnit::Seconds).too_low("0").convert(elapsed));
+    }
+
+    let rounded = if approximate { approximate_duration(elapsed) } else { elapsed };
+    if rounded.as_secs() == 0 {
+        // Too close to call either way, so timeago's word for "now"
+        formatter.convert(rounded)
+    } else if approximate {
+        directed(locale.about(&formatter.convert(rounded)))
+    } else {
+        directed(formatter.convert(rounded))
+    }
+}
+
+/// The text a banner shows, including its labels, without any template around it.
+pub fn banner_text(context: &RenderContext, now: DateTime<Utc>) -> String {
+    let text = match context.output_form {
+        OutputForm::Relative => format_relative_in(context.value, now, context.approximate, context.exact_seconds, context.locale, context.precision),
+        OutputForm::Absolute => match context.format {
+            Some(format) => context.value.with_timezone(&context.tz_offset).format(format).to_string(),
+            None => context.value.with_timezone(&context.tz_offset).to_rfc3339(),
+        },
+        OutputForm::Epoch { grouped } => format_epoch(context.value.timestamp(), grouped),
+        OutputForm::Week => format_iso_week(context.value, context.tz_offset),
+        OutputForm::BinaryClock | OutputForm::Digital { seconds: true } | OutputForm::Clock { .. } => context.value.with_timezone(&context.tz_offset).format("%H:%M:%S").to_string(),
+        OutputForm::Digital { seconds: false } => context.value.with_timezone(&context.tz_offset).format("%H:%M").to_string(),
+        OutputForm::Badge => format_compact_relative(context.value, now),
+        OutputForm::Iso(form) => format_iso(context.value, context.tz_offset, form),
+    };
+
+    // Labels are joined as given, so any spacing is up to the caller
+    let mut text = format!("{}{}{}", context.prefix.unwrap_or(""), text, context.suffix.unwrap_or(""));
+    if context.as_of && context.output_form == OutputForm::Absolute {
+        text.push_str(&format!(" (as of {})", now.to_rfc3339_opts(SecondsFormat::Secs, false)));
+    }
+
+    text
 }
 
 pub fn render_template(context: RenderContext) -> Result<String, tera::Error> {
     let mut template_context = Context::new();
-    let formatter = Formatter::new();
+    let text = banner_text(&context, context.reference_time());
+    template_context.insert("text", text.as_str());
+    template_context.insert("font_family", context.font);
+
+    if context.output_form == OutputForm::Badge {
+        template_context.insert("font_size", &badge_font_size(&text));
+    }
+
+    if context.output_form == OutputForm::BinaryClock {
+        let local = context.value.with_timezone(&context.tz_offset);
+        template_context.insert("dots", &binary_clock_dots(local.hour(), local.minute(), local.second()));
+    }
+
+    if let OutputForm::Clock { seconds } = context.output_form {
+        let local = context.value.with_timezone(&context.tz_offset);
+        template_context.insert("hands", &calculate_clock_hands(local.hour(), local.minute(), local.second(), context.clock_decimals));
+        template_context.insert("ticks", &clock_ticks(context.clock_decimals));
+        template_context.insert("seconds", &seconds);
+        template_context.insert("linecap", context.linecap.as_str());
+    }
 
-    template_context.insert("text", match context.output_form {
-        OutputForm::Relative => formatter.convert_chrono(context.value, Utc::now()),
-        OutputForm::Absolute => context.value.to_rfc3339()
-    }.as_str());
+    if let OutputForm::Digital { seconds } = context.output_form {
+        let local = context.value.with_timezone(&context.tz_offset);
+        template_context.insert("hours", &format!("{:02}", local.hour()));
+        template_context.insert("minutes", &format!("{:02}", local.minute()));
+        if seconds {
+            template_context.insert("seconds", &format!("{:02}", local.second()));
+        }
+    }
 
-    TEMPLATES.render("basic.svg", &template_context)
+    // Without an explicit foreground, pick whichever of black or white reads best on the background,
+    // leaving the theme to fill in anything still unset
+    let theme = context.theme.colors();
+    let background = context.background.or(theme.map(|(background, _)| background));
+    let text_color = context.foreground
+        .or_else(|| context.background.map(|bg| bg.contrasting_text()))
+        .or(theme.map(|(_, text)| text));
+    if let Some(background) = background {
+        template_context.insert("bg_color", &background.to_string());
+    }
+    if let Some(text_color) = text_color {
+        template_context.insert("text_color", &text_color.to_string());
+    }
+    // Automatic themes switch to the dark colors in the browser, unless colors were given explicitly
+    if context.theme == Theme::Auto && context.background.is_none() && context.foreground.is_none() {
+        template_context.insert("dark_bg_color", &DARK_BACKGROUND.to_string());
+        template_context.insert("dark_text_color", &DARK_TEXT.to_string());
+    }
+
+    // Rotated banners expand the viewport to the rotated bounds, centered on the original content
+    let (view_width, view_height) = banner_size(&context, &text);
+    template_context.insert("view_width", &view_width);
+    template_context.insert("view_height", &view_height);
+    template_context.insert("text_y", &basic_text_y(view_height));
+    let (view_width, view_height) = (view_width as f32, view_height as f32);
+    let (width, height) = rotated_bounds(view_width, view_height, context.rotate);
+    template_context.insert("view_box", &format!("{} {} {} {}", (view_width - width) / 2.0, (view_height - height) / 2.0, width, height));
+    if context.rotate != 0.0 {
+        template_context.insert("transform", &format!("rotate({} {} {})", context.rotate, view_width / 2.0, view_height / 2.0));
+    }
+
+    // Print output uses physical dimensions, leaving the pixel count up to the rasterizer's DPI
+    let (width, height) = match context.dpi {
+        Some(_) => (format!("{:.4}in", width / 96.0), format!("{:.4}in", height / 96.0)),
+        None => (width.to_string(), height.to_string()),
+    };
+    template_context.insert("width", &width);
+    template_context.insert("height", &height);
+
+    // Relative banners reveal the precise time on hover
+    if matches!(context.output_form, OutputForm::Relative | OutputForm::Badge) {
+        template_context.insert("title", &context.value.to_rfc3339());
+    }
+
+    templates().render(&format!("{}.svg", context.view), &template_context)
 }
 
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, FixedOffset, SecondsFormat, TimeZone, Utc};
+    use crate::color::{Rgb, Theme};
+    use crate::locale::Locale;
+    use crate::template::{parse_dimension, DEFAULT_FONT, DEFAULT_TEMPLATE_DIR, init_templates, load_templates, resolve_font, banner_text, binary_clock_dots, calculate_clock_hands, clock_ticks, ClockPoint, DEFAULT_CLOCK_DECIMALS, DiffUnit, format_compact_relative, format_difference, format_iso, IsoForm, LineCap, render_text, format_epoch, format_iso_week, format_relative, format_relative_in, MAX_PRECISION, OutputForm, parse_clock_decimals, parse_precision, render_sparkline, parse_rotation, render_template, RenderContext, sparkline_points, SparkPoint, validate_format, validate_label};
+
+    fn context(output_form: OutputForm, value: DateTime<Utc>) -> RenderContext<'static> {
+        RenderContext {
+            output_form,
+            value,
+            tz_offset: FixedOffset::east_opt(0).unwrap(),
+            tz_name: "UTC",
+            view: "basic",
+            approximate: false,
+            exact_seconds: false,
+            prefix: None,
+            suffix: None,
+            dpi: None,
+            background: None,
+            foreground: None,
+            rotate: 0.0,
+            as_of: false,
+            format: None,
+            now: None,
+            locale: Locale::English,
+            precision: 1,
+            theme: Theme::Light,
+            font: DEFAULT_FONT,
+            width: None,
+            height: None,
+            linecap: LineCap::Round,
+            clock_decimals: DEFAULT_CLOCK_DECIMALS,
+        }
+    }
+
+    #[test]
+    fn custom_template_dir() {
+        let dir = std::env::temp_dir().join(format!("time-banner-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("custom.svg"), "<svg><text>{{ text }}</text></svg>").unwrap();
+
+        let tera = load_templates(&format!("{}/", dir.display())).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(tera.get_template_names().collect::<Vec<_>>(), vec!["custom.svg"]);
+
+        let mut context = tera::Context::new();
+        context.insert("text", "<b>");
+        assert_eq!(tera.render("custom.svg", &context).unwrap(), "<svg><text>&lt;b&gt;</text></svg>");
+
+        assert!(load_templates(DEFAULT_TEMPLATE_DIR).unwrap().get_template_names().any(|name| name == "basic.svg"));
+    }
+
+    #[test]
+    fn broken_template_dir() {
+        let dir = std::env::temp_dir().join(format!("time-banner-broken-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_name = dir.display().to_string();
+
+        // Empty directories fail at startup rather than on the first render
+        assert_eq!(init_templates(&dir_name), Err(format!("No templates found in {}", dir_name)));
+
+        std::fs::write(dir.join("broken.svg"), "<svg>{{ text </svg>").unwrap();
+        let error = init_templates(&dir_name).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.starts_with(&format!("Failed to load templates from {}", dir_name)), "{}", error);
+        assert!(error.contains("broken.svg"), "{}", error);
+    }
+
+    #[test]
+    fn epoch_ungrouped() {
+        assert_eq!(format_epoch(0, false), "0");
+        assert_eq!(format_epoch(1752170474, false), "1752170474");
+        assert_eq!(format_epoch(-86400, false), "-86400");
+    }
+
+    #[test]
+    fn epoch_grouped() {
+        assert_eq!(format_epoch(0, true), "0");
+        assert_eq!(format_epoch(999, true), "999");
+        assert_eq!(format_epoch(1000, true), "1,000");
+        assert_eq!(format_epoch(1752170474, true), "1,752,170,474");
+        assert_eq!(format_epoch(-86400, true), "-86,400");
+    }
+
+    #[test]
+    fn iso_week() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(format_iso_week(Utc.with_ymd_and_hms(2024, 1, 17, 12, 0, 0).unwrap(), utc), "2024-W03");
+        assert_eq!(format_iso_week(Utc.with_ymd_and_hms(2023, 6, 14, 0, 0, 0).unwrap(), utc), "2023-W24");
+    }
+
+    #[test]
+    fn iso_week_year_boundary() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        // January 1st-3rd 2021 belong to the last week of 2020
+        assert_eq!(format_iso_week(Utc.with_ymd_and_hms(2021, 1, 3, 12, 0, 0).unwrap(), utc), "2020-W53");
+        assert_eq!(format_iso_week(Utc.with_ymd_and_hms(2021, 1, 4, 12, 0, 0).unwrap(), utc), "2021-W01");
+        // December 30th 2024 belongs to the first week of 2025
+        assert_eq!(format_iso_week(Utc.with_ymd_and_hms(2024, 12, 30, 12, 0, 0).unwrap(), utc), "2025-W01");
+    }
+
+    #[test]
+    fn iso_forms() {
+        let time = Utc.with_ymd_and_hms(2023, 6, 14, 15, 45, 30).unwrap();
+        let utc = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(format_iso(time, utc, IsoForm::Ordinal), "2023-165");
+        assert_eq!(format_iso(time, utc, IsoForm::Week), "2023-W24-3");
+        assert_eq!(format_iso(time, utc, IsoForm::Basic), "20230614T154530Z");
+
+        // The date rolls over in Japan
+        let jst = FixedOffset::east_opt(9 * 3600).unwrap();
+        assert_eq!(format_iso(time, jst, IsoForm::Ordinal), "2023-166");
+        assert_eq!(format_iso(time, jst, IsoForm::Week), "2023-W24-4");
+        assert_eq!(format_iso(time, jst, IsoForm::Basic), "20230615T004530+0900");
+
+        assert_eq!("Ordinal".parse::<IsoForm>(), Ok(IsoForm::Ordinal));
+        assert!("extended".parse::<IsoForm>().is_err());
+    }
+
+    #[test]
+    fn iso_week_offset() {
+        // 23:00 UTC on Sunday the 29th is already Monday the 30th in UTC+2
+        let time = Utc.with_ymd_and_hms(2024, 12, 29, 23, 0, 0).unwrap();
+        assert_eq!(format_iso_week(time, FixedOffset::east_opt(0).unwrap()), "2024-W52");
+        assert_eq!(format_iso_week(time, FixedOffset::east_opt(2 * 3600).unwrap()), "2025-W01");
+    }
+
+    #[test]
+    fn relative_tooltip() {
+        let value = Utc::now() - Duration::hours(1);
+        let rendered = render_template(context(OutputForm::Relative, value)).unwrap();
+
+        assert!(rendered.contains(">1 hour ago</text>"));
+        assert!(rendered.contains(&format!("<title>{}</title>", value.to_rfc3339())));
+    }
+
+    #[test]
+    fn absolute_has_no_tooltip() {
+        let rendered = render_template(context(OutputForm::Absolute, Utc::now())).unwrap();
+        assert!(!rendered.contains("<title>"));
+    }
+
+    #[test]
+    fn relative_exact_and_approximate() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+
+        let value = now - Duration::hours(2) - Duration::minutes(3);
+        assert_eq!(format_relative(value, now, false, false), "2 hours ago");
+        assert_eq!(format_relative(value, now, true, false), "about 2 hours ago");
+
+        let value = now - Duration::minutes(47);
+        assert_eq!(format_relative(value, now, false, false), "47 minutes ago");
+        assert_eq!(format_relative(value, now, true, false), "about 45 minutes ago");
+
+        let value = now - Duration::hours(2) - Duration::minutes(40);
+        assert_eq!(format_relative(value, now, true, false), "about 3 hours ago");
+
+        let value = now - Duration::minutes(2);
+        assert_eq!(format_relative(value, now, true, false), "now");
+    }
+
+    #[test]
+    fn relative_future() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+
+        assert_eq!(format_relative(now + Duration::hours(1), now, false, false), "in 1 hour");
+        assert_eq!(format_relative(now + Duration::hours(2) + Duration::minutes(40), now, true, false), "in about 3 hours");
+        assert_eq!(format_relative(now + Duration::seconds(8), now, false, true), "in 8 seconds");
+        // Rounded away entirely, it's just "now"
+        assert_eq!(format_relative(now + Duration::minutes(2), now, true, false), "now");
+    }
+
+    #[test]
+    fn relative_exact_seconds() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+
+        let value = now - Duration::seconds(8);
+        assert_eq!(format_relative(value, now, false, true), "8 seconds ago");
+        // Approximate mode would otherwise call this "now"
+        assert_eq!(format_relative(value, now, true, false), "now");
+        assert_eq!(format_relative(value, now, true, true), "8 seconds ago");
+        assert_eq!(format_relative(now, now, true, true), "0 seconds ago");
+        assert_eq!(format_relative(now - Duration::seconds(59), now, true, true), "59 seconds ago");
+
+        // Only sub-minute offsets are affected
+        assert_eq!(format_relative(now - Duration::minutes(47), now, true, true), "about 45 minutes ago");
+
+        let mut relative = context(OutputForm::Relative, Utc::now() - Duration::seconds(8));
+        relative.approximate = true;
+        relative.exact_seconds = true;
+        assert!(render_template(relative).unwrap().contains(">8 seconds ago</text>"));
+    }
+
+    #[test]
+    fn compact_relative() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        assert_eq!(format_compact_relative(now - Duration::seconds(8), now), "8s");
+        assert_eq!(format_compact_relative(now - Duration::minutes(59), now), "59m");
+        assert_eq!(format_compact_relative(now - Duration::hours(3) - Duration::minutes(50), now), "3h");
+        assert_eq!(format_compact_relative(now - Duration::days(2), now), "2d");
+        assert_eq!(format_compact_relative(now - Duration::days(20), now), "2w");
+        assert_eq!(format_compact_relative(now - Duration::days(800), now), "2y");
+        assert_eq!(format_compact_relative(now + Duration::hours(5), now), "5h");
+        assert_eq!(format_compact_relative(now, now), "0s");
+    }
+
+    #[test]
+    fn relative_badge() {
+        let mut badge = context(OutputForm::Badge, Utc::now() - Duration::hours(3) - Duration::minutes(5));
+        badge.view = OutputForm::Badge.view();
+        let rendered = render_template(badge).unwrap();
+        assert!(rendered.contains(r#"width="32" height="32""#));
+        assert!(rendered.contains(r#"viewBox="0 0 32 32""#));
+        assert!(rendered.contains(">3h</text>"));
+        assert!(rendered.contains(r#"font-size="20""#));
+
+        // Longer strings shrink to fit
+        let mut badge = context(OutputForm::Badge, Utc::now() - Duration::days(54_800));
+        badge.view = OutputForm::Badge.view();
+        let rendered = render_template(badge).unwrap();
+        assert!(rendered.contains(">150y</text>"));
+        assert!(rendered.contains(r#"font-size="11.6""#));
+    }
+
+    #[test]
+    fn binary_clock_lit_dots() {
+        let dots = binary_clock_dots(13, 45, 9);
+        assert_eq!(dots.len(), 24);
+
+        // Column-major, top row is the most significant bit
+        let lit: Vec<(usize, usize)> = dots.iter().enumerate()
+            .filter(|(_, dot)| dot.lit)
+            .map(|(i, _)| (i / 4, i % 4))
+            .collect();
+        assert_eq!(lit, vec![
+            (0, 3),         // 1
+            (1, 2), (1, 3), // 3
+            (2, 1),         // 4
+            (3, 1), (3, 3), // 5
+            (5, 0), (5, 3), // 9
+        ]);
+    }
+
+    #[test]
+    fn binary_clock_template() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 13, 45, 9).unwrap();
+        let mut binary = context(OutputForm::BinaryClock, value);
+        binary.view = OutputForm::BinaryClock.view();
+
+        let rendered = render_template(binary).unwrap();
+        assert!(rendered.contains("<title>13:45:09</title>"));
+        assert_eq!(rendered.matches("<circle").count(), 24);
+    }
+
+    #[test]
+    fn prefix_and_suffix() {
+        let value = Utc::now() - Duration::days(3);
+        let mut relative = context(OutputForm::Relative, value);
+        relative.prefix = Some("Released ");
+        relative.suffix = Some("!");
+        assert!(render_template(relative).unwrap().contains(">Released 3 days ago!</text>"));
+
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let mut absolute = context(OutputForm::Absolute, value);
+        absolute.prefix = Some("Since ");
+        absolute.suffix = Some(" <UTC>");
+        assert!(render_template(absolute).unwrap().contains(">Since 2023-06-14T12:00:00+00:00 &lt;UTC&gt;</text>"));
+    }
+
+    #[test]
+    fn as_of_caption() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let mut absolute = context(OutputForm::Absolute, value);
+        absolute.as_of = true;
+
+        let before = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+        let rendered = render_template(absolute).unwrap();
+        let after = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+        assert!(
+            [before, after].iter().any(|now| rendered.contains(&format!(">2023-06-14T12:00:00+00:00 (as of {})</text>", now))),
+            "{}", rendered
+        );
+
+        // Only absolute banners are captioned
+        let mut relative = context(OutputForm::Relative, value);
+        relative.as_of = true;
+        assert!(!render_template(relative).unwrap().contains("as of"));
+    }
+
+    #[test]
+    fn label_validation() {
+        assert_eq!(validate_label("Updated "), Ok("Updated "));
+        assert!(validate_label(&"x".repeat(65)).is_err());
+        assert!(validate_label("line\nbreak").is_err());
+    }
+
+    #[test]
+    fn physical_dimensions() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        assert!(render_template(context(OutputForm::Absolute, value)).unwrap().contains(r#"width="512" height="34""#));
+
+        let mut print = context(OutputForm::Absolute, value);
+        print.dpi = Some(300.0);
+        let rendered = render_template(print).unwrap();
+        assert!(rendered.contains(r#"width="5.3333in" height="0.3542in""#));
+        assert!(rendered.contains(r#"viewBox="0 0 512 34""#));
+    }
+
+    #[test]
+    fn sparkline_spacing() {
+        let points = sparkline_points(&[100, 200, 500]);
+        assert_eq!(points, vec![
+            SparkPoint { x: 4.0, y: 10.0 },
+            SparkPoint { x: 32.0, y: 10.0 },
+            SparkPoint { x: 116.0, y: 10.0 },
+        ]);
+
+        // Identical times collapse to the middle
+        assert!(sparkline_points(&[7, 7]).iter().all(|point| point.x == 60.0));
+        assert!(sparkline_points(&[]).is_empty());
+    }
+
+    #[test]
+    fn sparkline_template() {
+        let rendered = render_sparkline(&[1752170474, 1752000000, 1752100000, 1752170000]).unwrap();
+        assert_eq!(rendered.matches("<circle").count(), 4);
+        assert!(rendered.contains(r#"x1="4" y1="10" x2="116""#));
+    }
+
+    #[test]
+    fn automatic_text_color() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+
+        let mut dark = context(OutputForm::Absolute, value);
+        dark.background = "1e1e2e".parse::<Rgb>().ok();
+        let rendered = render_template(dark).unwrap();
+        assert!(rendered.contains(r##"fill="#1e1e2e""##));
+        assert!(rendered.contains(r##"<text x="8" y="27" fill="#ffffff">"##));
+
+        let mut light = context(OutputForm::Absolute, value);
+        light.background = "f5f5f5".parse::<Rgb>().ok();
+        assert!(render_template(light).unwrap().contains(r##"<text x="8" y="27" fill="#000000">"##));
+
+        // An explicit foreground always wins, even if it is hard to read
+        let mut explicit = context(OutputForm::Absolute, value);
+        explicit.background = "f5f5f5".parse::<Rgb>().ok();
+        explicit.foreground = "eeeeee".parse::<Rgb>().ok();
+        assert!(render_template(explicit).unwrap().contains(r##"<text x="8" y="27" fill="#eeeeee">"##));
+
+        assert!(render_template(context(OutputForm::Absolute, value)).unwrap().contains(r#"<text x="8" y="27">"#));
+    }
+
+    #[test]
+    fn rotation_bounds() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+
+        let mut quarter = context(OutputForm::Absolute, value);
+        quarter.rotate = 90.0;
+        let rendered = render_template(quarter).unwrap();
+        assert!(rendered.contains(r#"width="34" height="512" viewBox="239 -239 34 512""#));
+        assert!(rendered.contains(r#"<g transform="rotate(90 256 17)">"#));
+
+        let mut half = context(OutputForm::Absolute, value);
+        half.rotate = 180.0;
+        assert!(render_template(half).unwrap().contains(r#"width="512" height="34" viewBox="0 0 512 34""#));
+
+        assert!(render_template(context(OutputForm::Absolute, value)).unwrap().contains("<g>"));
+    }
+
+    #[test]
+    fn rotation_parsing() {
+        assert_eq!(parse_rotation("90"), Ok(90.0));
+        assert_eq!(parse_rotation("-90"), Ok(270.0));
+        assert_eq!(parse_rotation("450"), Ok(90.0));
+        assert!(parse_rotation("NaN").is_err());
+        assert!(parse_rotation("sideways").is_err());
+    }
+
+    #[test]
+    fn difference_single_unit() {
+        let a = Utc.timestamp_opt(0, 0).unwrap();
+        let b = Utc.timestamp_opt(51 * 3600 + 1800, 0).unwrap();
+
+        assert_eq!(format_difference(a, b, Some(DiffUnit::Hours), None), "51 hours");
+        assert_eq!(format_difference(b, a, Some(DiffUnit::Hours), None), "51 hours");
+        assert_eq!(format_difference(a, b, Some(DiffUnit::Days), None), "2 days");
+        assert_eq!(format_difference(a, Utc.timestamp_opt(86400, 0).unwrap(), Some(DiffUnit::Days), None), "1 day");
+    }
+
+    #[test]
+    fn difference_fractional() {
+        let a = Utc.timestamp_opt(0, 0).unwrap();
+        let b = Utc.timestamp_opt(181440, 0).unwrap();
+
+        assert_eq!(format_difference(a, b, Some(DiffUnit::Days), Some(1)), "2.1 days");
+        assert_eq!(format_difference(a, b, Some(DiffUnit::Weeks), Some(3)), "0.300 weeks");
+        assert_eq!(format_difference(a, b, Some(DiffUnit::Hours), Some(0)), "50 hours");
+    }
+
+    #[test]
+    fn difference_span_and_units() {
+        let a = Utc.timestamp_opt(0, 0).unwrap();
+        let b = Utc.timestamp_opt(2 * 86400 + 3 * 3600, 0).unwrap();
+        assert_eq!(format_difference(a, b, None, None), "2 days 3 hours");
+
+        assert_eq!("Hours".parse::<DiffUnit>(), Ok(DiffUnit::Hours));
+        assert_eq!("day".parse::<DiffUnit>(), Ok(DiffUnit::Days));
+        assert!("fortnights".parse::<DiffUnit>().is_err());
+
+        assert!(render_text("51 hours").unwrap().contains(">51 hours</text>"));
+    }
+
+    #[test]
+    fn font_selection() {
+        let installed = |family: &str| family == "Inter";
+        assert_eq!(resolve_font("Inter", installed), "Inter");
+        assert_eq!(resolve_font("monospace", installed), "monospace");
+        assert_eq!(resolve_font("Sans-Serif", installed), "Sans-Serif");
+        // Unknown and malformed families fall back instead of erroring
+        assert_eq!(resolve_font("Comic Sans MS", installed), DEFAULT_FONT);
+        assert_eq!(resolve_font("Inter\"/><script>", |_| true), DEFAULT_FONT);
+        assert_eq!(resolve_font("", |_| true), DEFAULT_FONT);
+
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let rendered = render_template(RenderContext { font: "monospace", ..context(OutputForm::Absolute, value) }).unwrap();
+        assert!(rendered.contains("font-family=\"monospace\""), "{}", rendered);
+    }
+
+    #[test]
+    fn banner_dimensions() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let long = RenderContext { suffix: Some(" America/Argentina/Buenos_Aires"), ..context(OutputForm::Absolute, value) };
+        let text = banner_text(&long, value);
+
+        // 56 characters of 16.2px glyphs, plus padding, no longer fit in 512px
+        assert_eq!(text.chars().count(), 56);
+        let rendered = render_template(long.clone()).unwrap();
+        assert!(rendered.contains(r#"width="924" height="34" viewBox="0 0 924 34""#), "{}", rendered);
+
+        let fixed = RenderContext { width: Some(300), height: Some(60), ..long };
+        let rendered = render_template(fixed).unwrap();
+        assert!(rendered.contains(r#"viewBox="0 0 300 60""#));
+        assert!(rendered.contains(r#"<text x="8" y="40""#), "{}", rendered);
+
+        assert_eq!(parse_dimension("640"), Ok(640));
+        assert!(parse_dimension("8").is_err());
+        assert!(parse_dimension("5000").is_err());
+        assert!(parse_dimension("wide").is_err());
+    }
+
+    #[test]
+    fn themes() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let themed = |theme, background| render_template(RenderContext { theme, background, ..context(OutputForm::Absolute, value) }).unwrap();
+
+        let dark = themed(Theme::Dark, None);
+        assert!(dark.contains("<rect width=\"512\" height=\"34\" fill=\"#0d1117\"/>"), "{}", dark);
+        assert!(dark.contains("fill=\"#e6edf3\">2023-06-14T12:00:00+00:00</text>"));
+        // Explicit colors win over the theme's
+        let custom = themed(Theme::Dark, Some(Rgb { r: 0xf5, g: 0xf5, b: 0xf5 }));
+        assert!(custom.contains("fill=\"#f5f5f5\"") && custom.contains("fill=\"#000000\""));
+        assert!(!custom.contains("#0d1117"));
+
+        let light = themed(Theme::Light, None);
+        assert!(!light.contains("<rect") && !light.contains("prefers-color-scheme"));
+
+        let auto = themed(Theme::Auto, None);
+        assert!(auto.contains("@media (prefers-color-scheme: dark)"), "{}", auto);
+        assert!(auto.contains("#0d1117") && auto.contains("#e6edf3"));
+        assert!(!themed(Theme::Auto, Some(Rgb { r: 0, g: 0, b: 0 })).contains("prefers-color-scheme"));
+    }
+
+    #[test]
+    fn clock_hands() {
+        let hands = calculate_clock_hands(3, 0, 30, 2);
+        // Thirty seconds points straight down
+        assert_eq!(hands.second, ClockPoint { x: 16.0, y: 28.0 });
+        // The minute hand has crept half a minute (3 degrees) past the hour
+        assert_eq!(hands.minute, ClockPoint { x: 16.58, y: 5.02 });
+        assert_eq!(calculate_clock_hands(0, 0, 0, 2).second, ClockPoint { x: 16.0, y: 4.0 });
+        assert_eq!(calculate_clock_hands(15, 0, 0, 2).hour, ClockPoint { x: 23.0, y: 16.0 });
+        assert_eq!(calculate_clock_hands(9, 0, 0, 2).hour, calculate_clock_hands(21, 0, 0, 2).hour);
+        assert_eq!(calculate_clock_hands(12, 45, 45, 2).second, ClockPoint { x: 4.0, y: 16.0 });
+
+        let ticks = clock_ticks(2);
+        assert_eq!(ticks.len(), 12);
+        assert_eq!(ticks[6].to, ClockPoint { x: 16.0, y: 30.0 });
+    }
+
+    #[test]
+    fn clock_seconds_hand() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 30).unwrap();
+        let mut clock = context(OutputForm::Clock { seconds: false }, value);
+        clock.view = "clock";
+        assert!(!render_template(clock.clone()).unwrap().contains("y2=\"28\""));
+
+        clock.output_form = OutputForm::Clock { seconds: true };
+        let rendered = render_template(clock.clone()).unwrap();
+        assert!(rendered.contains("x2=\"16\" y2=\"28\""), "{}", rendered);
+        assert!(rendered.contains("stroke-linecap=\"round\""), "{}", rendered);
+
+        let square = render_template(RenderContext { linecap: LineCap::Square, ..clock }).unwrap();
+        assert!(square.contains("stroke-linecap=\"square\"") && !square.contains("round"), "{}", square);
+    }
+
+    #[test]
+    fn clock_decimals() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 3, 0, 30).unwrap();
+        let clock = RenderContext { view: "clock", ..context(OutputForm::Clock { seconds: true }, value) };
+        let rendered = |clock_decimals| render_template(RenderContext { clock_decimals, ..clock.clone() }).unwrap();
+
+        // One place is a tenth of a pixel on the 32x32 face, and saves a byte on most coordinates
+        let (precise, rounded) = (rendered(2), rendered(1));
+        assert!(rounded.len() < precise.len(), "{} >= {}", rounded.len(), precise.len());
+        assert!(precise.contains("x2=\"16.58\" y2=\"5.02\""), "{}", precise);
+        assert!(rounded.contains("x2=\"16.6\" y2=\"5\""), "{}", rounded);
+        assert!(rendered(0).len() < rounded.len());
+
+        // Rounding never moves a hand by more than half a place
+        let (hands, coarse) = (calculate_clock_hands(3, 0, 30, 2), calculate_clock_hands(3, 0, 30, 1));
+        for (a, b) in [(hands.hour, coarse.hour), (hands.minute, coarse.minute), (hands.second, coarse.second)] {
+            assert!((a.x - b.x).abs() <= 0.051 && (a.y - b.y).abs() <= 0.051, "{:?} {:?}", a, b);
+        }
+
+        assert_eq!(parse_clock_decimals("1"), Ok(1));
+        assert!(parse_clock_decimals("5").is_err());
+        assert!(parse_clock_decimals("-1").is_err());
+        assert_eq!(DEFAULT_CLOCK_DECIMALS, 2);
+    }
+
+    #[test]
+    fn parse_linecap() {
+        assert_eq!("BUTT".parse::<LineCap>(), Ok(LineCap::Butt));
+        assert_eq!("square".parse::<LineCap>(), Ok(LineCap::Square));
+        assert!("rounded".parse::<LineCap>().is_err());
+    }
+
+    #[test]
+    fn digital_clock() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 30, 45).unwrap();
+        let mut digital = context(OutputForm::Digital { seconds: true }, value);
+        digital.view = OutputForm::Digital { seconds: true }.view();
+        digital.tz_offset = FixedOffset::east_opt(2 * 3600).unwrap();
+
+        let rendered = render_template(digital.clone()).unwrap();
+        assert!(rendered.contains(">14:30:45</text>"), "{}", rendered);
+        assert!(rendered.contains("viewBox=\"0 0 160 44\""));
+
+        let rendered = render_template(RenderContext { output_form: OutputForm::Digital { seconds: false }, ..digital }).unwrap();
+        assert!(rendered.contains(">14:30</text>"), "{}", rendered);
+        assert!(!rendered.contains("14:30:45"));
+    }
+
+    #[test]
+    fn relative_localized() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let relative = |value, approximate, locale| format_relative_in(value, now, approximate, false, locale, 1);
+
+        assert_eq!(relative(now - Duration::hours(2), false, Locale::Spanish), "hace 2 horas");
+        assert_eq!(relative(now + Duration::days(3), false, Locale::Spanish), "en 3 días");
+        assert_eq!(relative(now - Duration::hours(2), false, Locale::French), "il y a 2 heures");
+        assert_eq!(relative(now + Duration::minutes(170), true, Locale::French), "dans environ 3 heures");
+        assert_eq!(relative(now - Duration::minutes(47), true, Locale::Italian), "circa 45 minuti fa");
+        assert_eq!(relative(now, false, Locale::German), format_relative_in(now, now, true, false, Locale::German, 1));
+        assert_ne!(relative(now - Duration::hours(2), false, Locale::Spanish), format_relative(now - Duration::hours(2), now, false, false));
+    }
+
+    #[test]
+    fn relative_precision() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let value = now + Duration::hours(1) + Duration::minutes(5);
+        let mut relative = context(OutputForm::Relative, value);
+
+        assert_eq!(banner_text(&relative, now), "in 1 hour");
+        relative.precision = 2;
+        assert_eq!(banner_text(&relative, now), "in 1 hour 5 minutes");
+        assert_eq!(banner_text(&relative, now + Duration::hours(2)), "55 minutes ago");
+        assert_eq!(format_relative_in(now - Duration::seconds(3723), now, false, false, Locale::English, 3), "1 hour 2 minutes 3 seconds ago");
+
+        assert_eq!(parse_precision("2"), Ok(2));
+        assert_eq!(parse_precision("0"), Ok(1));
+        assert_eq!(parse_precision("99"), Ok(MAX_PRECISION));
+        assert!(parse_precision("two").is_err());
+    }
+
+    #[test]
+    fn fixed_reference_time() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let mut relative = context(OutputForm::Relative, now + Duration::hours(1));
+        relative.now = Some(now.timestamp());
+        assert_eq!(relative.reference_time(), now);
+
+        let rendered = render_template(relative).unwrap();
+        assert!(rendered.contains(">in 1 hour<"), "{}", rendered);
+    }
+
+    #[test]
+    fn custom_format() {
+        let value = Utc.with_ymd_and_hms(2023, 6, 14, 12, 0, 0).unwrap();
+        let formatted = |format| banner_text(&RenderContext { format: Some(format), ..context(OutputForm::Absolute, value) }, value);
+        assert_eq!(formatted("%Y-%m-%d %H:%M"), "2023-06-14 12:00");
+        assert_eq!(formatted("%A, %B %-d"), "Wednesday, June 14");
+
+        // Other forms ignore the format
+        let relative = RenderContext { format: Some("%Y"), ..context(OutputForm::Relative, value) };
+        assert_eq!(banner_text(&relative, value + Duration::hours(1)), "1 hour ago");
+    }
+
+    #[test]
+    fn format_validation() {
+        assert_eq!(validate_format("%Y-%m-%d"), Ok("%Y-%m-%d"));
+        assert_eq!(validate_format("plain text"), Ok("plain text"));
+        assert!(validate_format("%Q").is_err());
+        assert!(validate_format("%").is_err());
+        assert!(validate_format("%#z").is_err());
+        assert!(validate_format("%Y\n").is_err());
+        assert!(validate_format(&"%Y".repeat(40)).is_err());
+    }
+}